@@ -0,0 +1,234 @@
+/// Anotación de tipo parseada. Los nombres quedan tal como aparecen en el código (`models.Product`,
+/// `typing.Optional`); las forward refs (`"Product"`) se parsean como si no tuvieran comillas.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Annotation {
+    // `Product`, `list[Product]`, `typing.Optional["Product"]`
+    Name { name: String, args: Vec<Annotation> },
+    // `A | B`
+    Union(Vec<Annotation>),
+    // `[int, str]` (parámetros de `Callable`)
+    List(Vec<Annotation>),
+    // `...`, literales y todo lo que no se pudo interpretar
+    Other,
+}
+
+// Envoltorios cuyo primer argumento es el tipo del valor
+const WRAPPERS: &[&str] = &[
+    "Annotated", "ClassVar", "Final", "Type", "type", "Required", "NotRequired", "ReadOnly",
+];
+
+// Colecciones que al iterarlas producen su primer argumento (en los mapeos, las claves)
+const ITERABLES: &[&str] = &[
+    "list", "List", "set", "Set", "frozenset", "FrozenSet", "Sequence", "MutableSequence",
+    "Iterable", "Iterator", "Collection", "AbstractSet", "MutableSet", "Generator",
+    "deque", "Deque", "dict", "Dict", "Mapping", "MutableMapping", "defaultdict", "DefaultDict",
+    "OrderedDict",
+];
+
+// Nombres que nunca son clases del workspace
+const NOT_CLASSES: &[&str] = &["None", "Any", "object", "tuple", "Tuple", "Callable", "Literal"];
+
+/// Nombre sin el prefijo de `typing` / `collections.abc` (`typing.Optional` → `Optional`).
+fn unqualified(name: &str) -> &str {
+    ["typing.", "typing_extensions.", "collections.abc.", "collections."]
+        .iter()
+        .find_map(|prefix| name.strip_prefix(prefix))
+        .unwrap_or(name)
+}
+
+struct Parser<'a> {
+    src: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn rest(&self) -> &str {
+        &self.src[self.pos..]
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        let skipped = self.rest().len() - self.rest().trim_start().len();
+        self.pos += skipped;
+        self.rest().chars().next()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += c.len_utf8();
+            return true;
+        }
+        false
+    }
+
+    // union := primary ('|' primary)*
+    fn union(&mut self) -> Annotation {
+        let mut alternatives = vec![self.primary()];
+        while self.eat('|') {
+            alternatives.push(self.primary());
+        }
+        if alternatives.len() == 1 {
+            alternatives.pop().unwrap_or(Annotation::Other)
+        } else {
+            Annotation::Union(alternatives)
+        }
+    }
+
+    // args := union (',' union)* ']'
+    fn args(&mut self) -> Vec<Annotation> {
+        let mut args = vec![];
+        while !self.eat(']') && self.peek().is_some() {
+            args.push(self.union());
+            if !self.eat(',') {
+                self.eat(']');
+                break;
+            }
+        }
+        args
+    }
+
+    fn primary(&mut self) -> Annotation {
+        match self.peek() {
+            Some(quote @ ('"' | '\'')) => {
+                self.pos += 1;
+                let end = self.rest().find(quote).unwrap_or(self.rest().len());
+                let inner = &self.rest()[..end];
+                let annotation = Annotation::parse(inner);
+                self.pos = (self.pos + end + 1).min(self.src.len());
+                annotation
+            }
+            Some('[') => {
+                self.pos += 1;
+                Annotation::List(self.args())
+            }
+            Some(c) if c.is_alphabetic() || c == '_' => {
+                let len = self
+                    .rest()
+                    .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.'))
+                    .unwrap_or(self.rest().len());
+                let name = self.rest()[..len].trim_end_matches('.').to_string();
+                self.pos += len;
+                let args = if self.eat('[') { self.args() } else { vec![] };
+                Annotation::Name { name, args }
+            }
+            Some(_) => {
+                // `...`, números y cualquier otra cosa: se descarta hasta el próximo separador
+                let len = self
+                    .rest()
+                    .find([',', '|', '[', ']'])
+                    .unwrap_or(self.rest().len());
+                self.pos += len;
+                Annotation::Other
+            }
+            None => Annotation::Other,
+        }
+    }
+}
+
+impl Annotation {
+    /// Parsea una anotación. Nunca falla: lo que no se entiende queda como `Other`.
+    pub fn parse(src: &str) -> Annotation {
+        Parser { src, pos: 0 }.union()
+    }
+
+    /// Clases candidatas para un valor con esta anotación, sin repetir y en orden de aparición:
+    ///   "Optional[Product]"        → Product
+    ///   "Union[A, B]" / "A | B"    → A, B
+    ///   "Annotated[Product, ...]"  → Product
+    ///   "models.Product"           → models.Product
+    /// Las colecciones (`list[Product]`) no aportan candidatos: el valor es la colección.
+    pub fn classes(&self) -> Vec<String> {
+        let mut classes = vec![];
+        self.collect_classes(&mut classes);
+        classes
+    }
+
+    /// Clases candidatas para los elementos que produce iterar un valor con esta anotación:
+    /// `list[Product]` → Product, `dict[UserId, User]` → UserId, `tuple[A, B]` → A, B.
+    pub fn element_classes(&self) -> Vec<String> {
+        let mut classes = vec![];
+        self.collect_element_classes(&mut classes);
+        classes
+    }
+
+    fn collect_classes(&self, out: &mut Vec<String>) {
+        let Annotation::Name { name, args } = self else {
+            if let Annotation::Union(alternatives) = self {
+                alternatives.iter().for_each(|a| a.collect_classes(out));
+            }
+            return;
+        };
+        match unqualified(name) {
+            "Optional" | "Union" => args.iter().for_each(|a| a.collect_classes(out)),
+            wrapper if WRAPPERS.contains(&wrapper) => {
+                if let Some(inner) = args.first() {
+                    inner.collect_classes(out);
+                }
+            }
+            other if ITERABLES.contains(&other) || NOT_CLASSES.contains(&other) => {}
+            _ => {
+                if !out.contains(name) {
+                    out.push(name.clone());
+                }
+            }
+        }
+    }
+
+    fn collect_element_classes(&self, out: &mut Vec<String>) {
+        let Annotation::Name { name, args } = self else {
+            if let Annotation::Union(alternatives) = self {
+                alternatives.iter().for_each(|a| a.collect_element_classes(out));
+            }
+            return;
+        };
+        match unqualified(name) {
+            "Optional" | "Union" => args.iter().for_each(|a| a.collect_element_classes(out)),
+            "tuple" | "Tuple" => args.iter().for_each(|a| a.collect_classes(out)),
+            wrapper if WRAPPERS.contains(&wrapper) => {
+                if let Some(inner) = args.first() {
+                    inner.collect_element_classes(out);
+                }
+            }
+            iterable if ITERABLES.contains(&iterable) => {
+                if let Some(element) = args.first() {
+                    element.collect_classes(out);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn classes(src: &str) -> Vec<String> {
+        Annotation::parse(src).classes()
+    }
+
+    #[test]
+    fn classes_unwrap_optionals_unions_and_forward_refs() {
+        assert_eq!(classes("Product"), vec!["Product"]);
+        assert_eq!(classes("Optional[Product]"), vec!["Product"]);
+        assert_eq!(classes("typing.Optional[\"Product\"]"), vec!["Product"]);
+        assert_eq!(classes("Product | None"), vec!["Product"]);
+        assert_eq!(classes("'Product'"), vec!["Product"]);
+        assert_eq!(classes("Union[Cart, 'Order', None]"), vec!["Cart", "Order"]);
+        assert_eq!(classes("Annotated[models.Product, Field(gt=0)]"), vec!["models.Product"]);
+        assert_eq!(classes("Repository[User]"), vec!["Repository"]);
+        assert!(classes("list[Product]").is_empty());
+        assert!(classes("Callable[[Product], None]").is_empty());
+        assert!(classes("").is_empty());
+    }
+
+    #[test]
+    fn element_classes_look_inside_iterables() {
+        let elements = |src: &str| Annotation::parse(src).element_classes();
+        assert_eq!(elements("list[Product]"), vec!["Product"]);
+        assert_eq!(elements("Optional[typing.Sequence['shop.Product']]"), vec!["shop.Product"]);
+        assert_eq!(elements("dict[UserId, User]"), vec!["UserId"]);
+        assert_eq!(elements("tuple[Cart, ...]"), vec!["Cart"]);
+        assert_eq!(elements("list[Cart | Order]"), vec!["Cart", "Order"]);
+        assert!(elements("Product").is_empty());
+    }
+}
@@ -0,0 +1,113 @@
+use crate::model::FileAnalysis;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// Índice de dependencias entre archivos: importer → archivos (o paquetes) que importa.
+/// Se usa en sentido inverso para saber qué archivos hay que re-resolver cuando cambia uno.
+#[derive(Debug, Default)]
+pub struct DependencyIndex {
+    imports: HashMap<PathBuf, HashSet<PathBuf>>,
+}
+
+/// Extrae los paths resueltos de los imports de un archivo analizado (ignora los no resueltos).
+fn imported_paths(analysis: &FileAnalysis) -> HashSet<PathBuf> {
+    analysis
+        .imports
+        .iter()
+        .filter_map(|import| import.path.as_deref())
+        .filter(|p| !p.is_empty())
+        .map(PathBuf::from)
+        .collect()
+}
+
+impl DependencyIndex {
+    /// Registra (o reemplaza) los imports de `importer` a partir de su análisis.
+    pub fn update(&mut self, importer: &Path, analysis: &FileAnalysis) {
+        self.imports.insert(importer.to_path_buf(), imported_paths(analysis));
+    }
+
+    /// Quita a `importer` del índice (por ejemplo, cuando el archivo se borra).
+    pub fn remove(&mut self, importer: &Path) {
+        self.imports.remove(importer);
+    }
+
+    /// Archivos que importan `path` directamente (o importan el paquete que lo contiene).
+    pub fn importers_of(&self, path: &Path) -> Vec<PathBuf> {
+        let mut importers: Vec<PathBuf> = self
+            .imports
+            .iter()
+            .filter(|(importer, imported)| {
+                importer.as_path() != path && imported.iter().any(|i| path.starts_with(i))
+            })
+            .map(|(importer, _)| importer.clone())
+            .collect();
+        importers.sort();
+        importers
+    }
+
+    /// Cierre transitivo de importers de `path`. Sólo se sigue propagando a través de los
+    /// archivos para los que `propagates` es `true` (los que pueden re-exportar tipos vía
+    /// `return_type`, de modo que una cadena `a().b()` en un tercer archivo pase por ellos).
+    pub fn dependents_of(&self, path: &Path, propagates: impl Fn(&Path) -> bool) -> Vec<PathBuf> {
+        let mut visited: HashSet<PathBuf> = HashSet::from([path.to_path_buf()]);
+        let mut result: Vec<PathBuf> = vec![];
+        let mut pending: Vec<PathBuf> = vec![path.to_path_buf()];
+
+        while let Some(current) = pending.pop() {
+            for importer in self.importers_of(&current) {
+                if !visited.insert(importer.clone()) {
+                    continue;
+                }
+                if propagates(&importer) {
+                    pending.push(importer.clone());
+                }
+                result.push(importer);
+            }
+        }
+
+        result.sort();
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Import;
+
+    fn importing(paths: &[&str]) -> FileAnalysis {
+        FileAnalysis {
+            imports: paths
+                .iter()
+                .map(|p| Import { name: "m".to_string(), path: Some(p.to_string()) })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn dependents_of_follows_importers_transitively() {
+        let mut index = DependencyIndex::default();
+        index.update(Path::new("/ws/shop.py"), &importing(&["/ws/product.py"]));
+        index.update(Path::new("/ws/main.py"), &importing(&["/ws/shop.py"]));
+        index.update(Path::new("/ws/other.py"), &importing(&[]));
+
+        assert_eq!(index.importers_of(Path::new("/ws/product.py")), vec![PathBuf::from("/ws/shop.py")]);
+
+        let all = index.dependents_of(Path::new("/ws/product.py"), |_| true);
+        assert_eq!(all, vec![PathBuf::from("/ws/main.py"), PathBuf::from("/ws/shop.py")]);
+
+        let direct_only = index.dependents_of(Path::new("/ws/product.py"), |_| false);
+        assert_eq!(direct_only, vec![PathBuf::from("/ws/shop.py")]);
+    }
+
+    #[test]
+    fn importers_of_matches_package_imports_and_removals() {
+        let mut index = DependencyIndex::default();
+        index.update(Path::new("/ws/main.py"), &importing(&["/ws/projecto"]));
+        assert_eq!(index.importers_of(Path::new("/ws/projecto/order.py")).len(), 1);
+
+        index.remove(Path::new("/ws/main.py"));
+        assert!(index.importers_of(Path::new("/ws/projecto/order.py")).is_empty());
+    }
+}
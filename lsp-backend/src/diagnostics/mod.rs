@@ -0,0 +1,138 @@
+use crate::navigation::line_range;
+use crate::quarantine::QuarantinedFile;
+use crate::{Connections, FunctionsInFiles};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, Position, Range};
+
+const SOURCE: &str = "lsp-backend";
+
+/// Warning sobre la línea de cada función definida que nunca se usa.
+fn unused_function_diagnostic(f: &FunctionsInFiles) -> Diagnostic {
+    let line = (f.line.max(1) - 1) as u32;
+    Diagnostic {
+        range: Range {
+            start: Position { line, character: 0 },
+            end: Position { line, character: u32::MAX },
+        },
+        severity: Some(DiagnosticSeverity::WARNING),
+        message: format!("Function '{}' is defined but never used", f.symbol),
+        source: Some(SOURCE.to_string()),
+        ..Default::default()
+    }
+}
+
+/// Error sobre un call site cuyo archivo destino fue borrado.
+fn dangling_call_diagnostic(c: &Connections, root: &Path) -> Diagnostic {
+    let module = Path::new(&c.target.module);
+    let module = module.strip_prefix(root).unwrap_or(module);
+    Diagnostic {
+        range: line_range(c.line, c.start_col, c.end_col),
+        severity: Some(DiagnosticSeverity::ERROR),
+        message: format!(
+            "Call to '{}' calls function from deleted module '{}'",
+            c.target,
+            module.display()
+        ),
+        source: Some(SOURCE.to_string()),
+        ..Default::default()
+    }
+}
+
+/// Error al inicio de un archivo en cuarentena: su análisis falló y el grafo usa el último válido.
+fn quarantine_diagnostic(q: &QuarantinedFile) -> Diagnostic {
+    Diagnostic {
+        range: Range {
+            start: Position { line: 0, character: 0 },
+            end: Position { line: 0, character: u32::MAX },
+        },
+        severity: Some(DiagnosticSeverity::ERROR),
+        message: format!(
+            "File could not be analyzed ({}); dependency data for it may be stale",
+            q.reason
+        ),
+        source: Some(SOURCE.to_string()),
+        ..Default::default()
+    }
+}
+
+/// Arma todos los diagnósticos del workspace agrupados por archivo: archivos en cuarentena,
+/// funciones sin uso y llamadas a funciones de módulos borrados.
+pub fn workspace_diagnostics<'a>(
+    unused_functions: &[FunctionsInFiles],
+    connections: &[Connections],
+    quarantined: impl IntoIterator<Item = &'a QuarantinedFile>,
+    root: &Path,
+) -> HashMap<String, Vec<Diagnostic>> {
+    let mut by_file: HashMap<String, Vec<Diagnostic>> = HashMap::new();
+
+    for q in quarantined {
+        by_file
+            .entry(q.path.clone())
+            .or_default()
+            .push(quarantine_diagnostic(q));
+    }
+
+    let mut seen = HashSet::new();
+    for f in unused_functions
+        .iter()
+        .filter(|f| seen.insert((&f.symbol, f.line)))
+    {
+        by_file
+            .entry(f.symbol.module.clone())
+            .or_default()
+            .push(unused_function_diagnostic(f));
+    }
+
+    for c in connections.iter().filter(|c| c.dangling) {
+        by_file
+            .entry(c.file_use.clone())
+            .or_default()
+            .push(dangling_call_diagnostic(c, root));
+    }
+
+    by_file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::EdgeKind;
+    use crate::symbol::SymbolId;
+
+    #[test]
+    fn workspace_diagnostics_reports_dangling_calls_on_the_caller() {
+        let connections = vec![Connections {
+            file_use: "/ws/main.py".to_string(),
+            line: 4,
+            start_col: 4,
+            end_col: 15,
+            target: SymbolId::function("/ws/projecto/product.py", "make_product"),
+            caller: SymbolId::function("/ws/main.py", "main"),
+            dangling: true,
+            kind: EdgeKind::Calls,
+        }];
+
+        let by_file = workspace_diagnostics(&[], &connections, [], Path::new("/ws"));
+        let diagnostics = &by_file["/ws/main.py"];
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::ERROR));
+        assert!(diagnostics[0].message.contains("deleted module 'projecto/product.py'"));
+        assert_eq!(diagnostics[0].range.start, Position { line: 3, character: 4 });
+    }
+
+    #[test]
+    fn workspace_diagnostics_reports_quarantined_files() {
+        let quarantined = QuarantinedFile {
+            path: "/ws/broken.py".to_string(),
+            reason: "malformed analysis: expected value at line 1 column 1".to_string(),
+            since: "2026-01-01T00:00:00Z".to_string(),
+        };
+
+        let by_file = workspace_diagnostics(&[], &[], [&quarantined], Path::new("/ws"));
+        let diagnostics = &by_file["/ws/broken.py"];
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::ERROR));
+        assert!(diagnostics[0].message.contains("malformed analysis"));
+    }
+}
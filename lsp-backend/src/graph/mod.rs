@@ -0,0 +1,289 @@
+use crate::Connections;
+use crate::hierarchy::Inheritance;
+use crate::navigation::line_range;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use tower_lsp::lsp_types::{Position, Range};
+
+/// Path relativo al workspace, tal como lo ve el frontend.
+pub fn relativize(path: &str, root: &Path) -> String {
+    let path = Path::new(path);
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Tipo de relación que representa una arista del grafo.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum EdgeKind {
+    // Llamada de una función o método a otro
+    #[default]
+    Calls,
+    // Subclase → clase base
+    Inherits,
+    // Instanciación (`Product(...)`) → la clase y su `__init__`
+    Instantiates,
+}
+
+/// Relación resuelta por el backend tal como la dibuja el frontend: origen, destino, y archivo y
+/// rango donde aparece (el call site, o el nombre de la subclase en una herencia). Las llamadas
+/// salen de las mismas `Connections` que usan references y rename, así el grafo coincide con la navegación.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct GraphEdge {
+    #[serde(default)]
+    pub kind: EdgeKind,
+    pub source: String,
+    pub target: String,
+    pub file: String,
+    pub range: Range,
+    // El archivo destino fue borrado: el frontend puede marcar la arista como rota
+    pub dangling: bool,
+}
+
+impl GraphEdge {
+    pub fn from_connection(connection: &Connections, root: &Path) -> GraphEdge {
+        GraphEdge {
+            kind: connection.kind,
+            source: connection.caller.node_id(root),
+            target: connection.target.node_id(root),
+            file: relativize(&connection.file_use, root),
+            range: line_range(connection.line, connection.start_col, connection.end_col),
+            dangling: connection.dangling,
+        }
+    }
+
+    pub fn from_inheritance(inheritance: &Inheritance, root: &Path) -> GraphEdge {
+        GraphEdge {
+            kind: EdgeKind::Inherits,
+            source: inheritance.subclass.node_id(root),
+            target: inheritance.base.node_id(root),
+            file: relativize(&inheritance.subclass.module, root),
+            range: line_range(inheritance.line, inheritance.start_col, inheritance.end_col),
+            dangling: false,
+        }
+    }
+
+    fn sort_key(&self) -> (&str, &str, &str, Position, Position, bool) {
+        (
+            &self.file,
+            &self.source,
+            &self.target,
+            self.range.start,
+            self.range.end,
+            self.dangling,
+        )
+    }
+}
+
+// `Range` no implementa `Ord`: ordenamos por una clave explícita para poder usar `BTreeSet`
+impl Ord for GraphEdge {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.kind
+            .cmp(&other.kind)
+            .then_with(|| self.sort_key().cmp(&other.sort_key()))
+    }
+}
+
+impl PartialOrd for GraphEdge {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Diferencia entre dos revisiones consecutivas del grafo que se envía al cliente.
+/// `base_revision` es la revisión sobre la que aplica: si el cliente no la tiene, debe pedir un snapshot.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct GraphDelta<E> {
+    pub revision: u64,
+    pub base_revision: u64,
+    pub added_files: Vec<Value>,
+    pub updated_files: Vec<Value>,
+    pub removed_files: Vec<String>,
+    pub added_edges: Vec<E>,
+    pub removed_edges: Vec<E>,
+}
+
+/// Último grafo enviado al cliente (archivos por `file_name` y aristas) y su revisión.
+#[derive(Debug)]
+pub struct GraphState<E> {
+    revision: u64,
+    files: BTreeMap<String, Value>,
+    edges: BTreeSet<E>,
+}
+
+impl<E> Default for GraphState<E> {
+    fn default() -> Self {
+        GraphState {
+            revision: 0,
+            files: BTreeMap::new(),
+            edges: BTreeSet::new(),
+        }
+    }
+}
+
+impl<E: Clone + Ord> GraphState<E> {
+    /// Compara el grafo actual con el último enviado. Si cambió, avanza la revisión y retorna el delta.
+    pub fn advance(
+        &mut self,
+        files: BTreeMap<String, Value>,
+        edges: BTreeSet<E>,
+    ) -> Option<GraphDelta<E>> {
+        let mut added_files = vec![];
+        let mut updated_files = vec![];
+        for (name, file) in &files {
+            match self.files.get(name) {
+                None => added_files.push(file.clone()),
+                Some(previous) if previous != file => updated_files.push(file.clone()),
+                Some(_) => {}
+            }
+        }
+        let removed_files: Vec<String> = self
+            .files
+            .keys()
+            .filter(|name| !files.contains_key(*name))
+            .cloned()
+            .collect();
+        let added_edges: Vec<E> = edges.difference(&self.edges).cloned().collect();
+        let removed_edges: Vec<E> = self.edges.difference(&edges).cloned().collect();
+
+        if added_files.is_empty()
+            && updated_files.is_empty()
+            && removed_files.is_empty()
+            && added_edges.is_empty()
+            && removed_edges.is_empty()
+        {
+            return None;
+        }
+
+        let base_revision = self.revision;
+        self.revision += 1;
+        self.files = files;
+        self.edges = edges;
+        Some(GraphDelta {
+            revision: self.revision,
+            base_revision,
+            added_files,
+            updated_files,
+            removed_files,
+            added_edges,
+            removed_edges,
+        })
+    }
+
+    /// Registra que el cliente recibió el grafo completo y retorna su revisión
+    /// (la misma si nada cambió desde el último envío).
+    pub fn replace(&mut self, files: BTreeMap<String, Value>, edges: BTreeSet<E>) -> u64 {
+        if self.files != files || self.edges != edges {
+            self.revision += 1;
+            self.files = files;
+            self.edges = edges;
+        }
+        self.revision
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::symbol::SymbolId;
+    use serde_json::json;
+
+    fn files(entries: &[(&str, Value)]) -> BTreeMap<String, Value> {
+        entries
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.clone()))
+            .collect()
+    }
+
+    fn edges(entries: &[&str]) -> BTreeSet<String> {
+        entries.iter().map(|e| e.to_string()).collect()
+    }
+
+    #[test]
+    fn advance_reports_file_and_edge_diffs_with_increasing_revisions() {
+        let mut state: GraphState<String> = GraphState::default();
+        let a = json!({ "file_name": "a.py", "functions": [] });
+        let b = json!({ "file_name": "b.py", "functions": [] });
+
+        let first = state
+            .advance(files(&[("a.py", a.clone()), ("b.py", b.clone())]), edges(&["a->b"]))
+            .unwrap();
+        assert_eq!((first.base_revision, first.revision), (0, 1));
+        assert_eq!(first.added_files.len(), 2);
+        assert_eq!(first.added_edges, vec!["a->b".to_string()]);
+
+        // Sin cambios no hay delta ni se avanza la revisión
+        assert!(state
+            .advance(files(&[("a.py", a.clone()), ("b.py", b.clone())]), edges(&["a->b"]))
+            .is_none());
+
+        let a2 = json!({ "file_name": "a.py", "functions": [{ "name": "f" }] });
+        let second = state.advance(files(&[("a.py", a2.clone())]), edges(&["a->a"])).unwrap();
+        assert_eq!((second.base_revision, second.revision), (1, 2));
+        assert!(second.added_files.is_empty());
+        assert_eq!(second.updated_files, vec![a2]);
+        assert_eq!(second.removed_files, vec!["b.py".to_string()]);
+        assert_eq!(second.added_edges, vec!["a->a".to_string()]);
+        assert_eq!(second.removed_edges, vec!["a->b".to_string()]);
+    }
+
+    #[test]
+    fn replace_only_bumps_revision_when_graph_changed() {
+        let mut state: GraphState<String> = GraphState::default();
+        let a = json!({ "file_name": "a.py" });
+        assert_eq!(state.replace(files(&[("a.py", a.clone())]), edges(&[])), 1);
+        assert_eq!(state.replace(files(&[("a.py", a.clone())]), edges(&[])), 1);
+        assert_eq!(state.replace(files(&[]), edges(&[])), 2);
+    }
+
+    #[test]
+    fn graph_edge_uses_frontend_ids_and_call_site_range() {
+        let connection = Connections {
+            file_use: "/ws/main.py".to_string(),
+            line: 4,
+            start_col: 4,
+            end_col: 15,
+            target: SymbolId::method("/ws/projecto/product.py", "Product", "price"),
+            caller: SymbolId::method("/ws/main.py", "Cart", "checkout"),
+            dangling: false,
+            kind: EdgeKind::Calls,
+        };
+
+        let edge = GraphEdge::from_connection(&connection, Path::new("/ws"));
+        assert_eq!(edge.kind, EdgeKind::Calls);
+        assert_eq!(edge.source, "mth::main.py::Cart::checkout");
+        assert_eq!(edge.target, "mth::projecto/product.py::Product::price");
+        assert_eq!(edge.file, "main.py");
+        assert_eq!(edge.range.start.line, 3);
+        assert_eq!(edge.range.start.character, 4);
+    }
+
+    #[test]
+    fn inheritance_edges_link_class_nodes() {
+        let inheritance = Inheritance {
+            subclass: SymbolId::class("/ws/shop/order.py", "Order"),
+            base: SymbolId::class("/ws/base.py", "BaseModel"),
+            line: 3,
+            start_col: 6,
+            end_col: 11,
+        };
+
+        let edge = GraphEdge::from_inheritance(&inheritance, Path::new("/ws"));
+        assert_eq!(edge.kind, EdgeKind::Inherits);
+        assert_eq!(edge.source, "cls::shop/order.py::Order");
+        assert_eq!(edge.target, "cls::base.py::BaseModel");
+        assert_eq!(edge.file, "shop/order.py");
+        assert_eq!(edge.range.start.line, 2);
+    }
+
+    #[test]
+    fn relativize_strips_workspace_root() {
+        assert_eq!(relativize("/ws/pkg/a.py", Path::new("/ws")), "pkg/a.py");
+        assert_eq!(relativize("/other/a.py", Path::new("/ws")), "/other/a.py");
+    }
+}
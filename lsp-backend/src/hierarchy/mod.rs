@@ -0,0 +1,492 @@
+use crate::annotation::Annotation;
+use crate::model::{ClassDef, FileAnalysis, FunctionDef};
+use crate::symbol::SymbolId;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Relación de herencia entre dos clases del workspace, con la posición del nombre de la subclase.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Inheritance {
+    pub subclass: SymbolId,
+    pub base: SymbolId,
+    pub line: i64,
+    pub start_col: usize,
+    pub end_col: usize,
+}
+
+/// Jerarquía de clases del workspace construida a partir de las `bases` que informa el analizador.
+/// Las bases que no se encuentran en el workspace (`object`, clases de librerías) se ignoran.
+/// Se construye una vez por snapshot del store y se comparte entre todos los archivos que se resuelven.
+pub struct ClassHierarchy<'a> {
+    store: &'a HashMap<PathBuf, FileAnalysis>,
+    // Paths del store en orden estable, para que la búsqueda no dependa del orden del HashMap
+    paths: Vec<&'a PathBuf>,
+    // Archivos que definen cada nombre de clase, en orden de path
+    files_by_class: HashMap<&'a str, Vec<&'a PathBuf>>,
+    // MROs ya calculados: el mismo receptor se consulta en cada llamada de cada archivo
+    mro_cache: RefCell<HashMap<SymbolId, Vec<SymbolId>>>,
+}
+
+impl<'a> ClassHierarchy<'a> {
+    pub fn new(store: &'a HashMap<PathBuf, FileAnalysis>) -> Self {
+        let mut paths: Vec<&PathBuf> = store.keys().collect();
+        paths.sort();
+        let mut files_by_class: HashMap<&str, Vec<&PathBuf>> = HashMap::new();
+        for path in &paths {
+            for class in &store[*path].classes {
+                files_by_class.entry(class.name.as_str()).or_default().push(path);
+            }
+        }
+        ClassHierarchy { store, paths, files_by_class, mro_cache: RefCell::default() }
+    }
+
+    /// Archivo que define la clase `name` vista desde `from`: primero los archivos que `from`
+    /// importa, luego el propio archivo y por último el resto del workspace.
+    pub fn find_class_file(&self, from: &Path, name: &str) -> Option<String> {
+        let candidates = self.files_by_class.get(name)?;
+
+        let mut imported: Vec<&Path> = self
+            .store
+            .get(from)
+            .into_iter()
+            .flat_map(|a| &a.imports)
+            .filter_map(|import| import.path.as_deref().map(Path::new))
+            .filter(|path| candidates.iter().any(|c| c.as_path() == *path))
+            .collect();
+        imported.sort();
+
+        imported
+            .first()
+            .copied()
+            .or_else(|| candidates.iter().find(|c| c.as_path() == from).map(|c| c.as_path()))
+            .or_else(|| candidates.first().map(|c| c.as_path()))
+            .map(|path| path.to_string_lossy().into_owned())
+    }
+
+    /// Definición de la clase `class` (un `SymbolId` de tipo clase).
+    pub fn class(&self, class: &SymbolId) -> Option<&'a ClassDef> {
+        self.store
+            .get(Path::new(&class.module))?
+            .class(&class.name)
+    }
+
+    /// Bases directas de `class` que existen en el workspace, en el orden en que se declaran.
+    pub fn bases(&self, class: &SymbolId) -> Vec<SymbolId> {
+        let Some(def) = self.class(class) else {
+            return vec![];
+        };
+        // `models.Base` y `Generic[T]` se resuelven como anotaciones desde el archivo de la subclase
+        def.bases
+            .iter()
+            .flat_map(|base| self.annotation_classes(Path::new(&class.module), base))
+            .collect()
+    }
+
+    /// Orden de resolución de métodos (C3, como Python) de `class`, empezando por ella misma.
+    pub fn mro(&self, class: &SymbolId) -> Vec<SymbolId> {
+        if let Some(mro) = self.mro_cache.borrow().get(class) {
+            return mro.clone();
+        }
+        let mro = self.linearize(class, &mut vec![]);
+        self.mro_cache.borrow_mut().insert(class.clone(), mro.clone());
+        mro
+    }
+
+    fn linearize(&self, class: &SymbolId, visiting: &mut Vec<SymbolId>) -> Vec<SymbolId> {
+        // Herencia cíclica (código inválido): cortamos acá
+        if visiting.contains(class) {
+            return vec![];
+        }
+        // Sólo se cachean MROs completos (calculados desde `mro`), así que se pueden reusar
+        if let Some(mro) = self.mro_cache.borrow().get(class) {
+            return mro.clone();
+        }
+        visiting.push(class.clone());
+        let bases = self.bases(class);
+        let mut sequences: Vec<Vec<SymbolId>> =
+            bases.iter().map(|base| self.linearize(base, visiting)).collect();
+        sequences.push(bases);
+        visiting.pop();
+
+        let mut mro = vec![class.clone()];
+        match c3_merge(sequences.clone()) {
+            Some(rest) => mro.extend(rest),
+            // Jerarquía inconsistente (Python la rechazaría): profundidad de izquierda a derecha sin repetidos
+            None => {
+                for candidate in sequences.into_iter().flatten() {
+                    if !mro.contains(&candidate) {
+                        mro.push(candidate);
+                    }
+                }
+            }
+        }
+        mro
+    }
+
+    /// Clase que define el método `method` para instancias de `class`: la primera de su MRO que lo tenga.
+    pub fn find_method_owner(&self, class: &SymbolId, method: &str) -> Option<SymbolId> {
+        self.mro(class)
+            .into_iter()
+            .find(|c| self.class(c).is_some_and(|def| def.method(method).is_some()))
+    }
+
+    /// Clases del workspace a las que puede apuntar el atributo de instancia `attribute` de `class`
+    /// (varias si está anotado con una unión). Se busca en cada clase del MRO: primero las anotaciones
+    /// del cuerpo de la clase (incluye campos de dataclass) y luego la asignación `self.<attribute> = ...`
+    /// de `__init__`.
+    pub fn attribute_types(&self, class: &SymbolId, attribute: &str) -> Vec<SymbolId> {
+        self.mro(class)
+            .iter()
+            .find_map(|owner| self.declared_attribute_types(owner, attribute))
+            .unwrap_or_default()
+    }
+
+    fn declared_attribute_types(&self, class: &SymbolId, attribute: &str) -> Option<Vec<SymbolId>> {
+        let def = self.class(class)?;
+        let module = Path::new(&class.module);
+        let annotated = |annotation: Option<&str>| non_empty(self.annotation_classes(module, annotation?));
+
+        if let Some(types) = annotated(def.attribute(attribute).and_then(|a| a.annotation.as_deref())) {
+            return Some(types);
+        }
+
+        let init = def.method("__init__")?;
+        let assignment = init.local_variable(&format!("self.{attribute}"))?;
+        if let Some(types) = annotated(assignment.annotation.as_deref()) {
+            return Some(types);
+        }
+        // `self.repo = repo`: tipo anotado del parámetro de `__init__`
+        let source = assignment.assigned_from.as_deref().unwrap_or(attribute);
+        if let Some(types) = annotated(init.parameter(source).and_then(|p| p.param_type.as_deref())) {
+            return Some(types);
+        }
+        let source = assignment.assigned_from.as_deref()?;
+        // `self.repo = UserRepository()`: constructor de una clase del workspace;
+        // `self.repo = make_repo()`: return_type de la función, resuelto en el archivo que la define
+        annotated(Some(source)).or_else(|| {
+            let (file, return_type) = self.function_return_type(module, init, source)?;
+            non_empty(self.annotation_classes(&file, return_type))
+        })
+    }
+
+    /// `return_type` de la función `name` llamada desde `caller` (en `module`), junto con el archivo
+    /// que la define: el importado si la llamada trae `import_name`, el mismo si no.
+    fn function_return_type(&self, module: &Path, caller: &FunctionDef, name: &str) -> Option<(PathBuf, &'a str)> {
+        let analysis = self.store.get(module)?;
+        let import_name = caller
+            .function_calls
+            .iter()
+            .find(|c| c.name == name)
+            .and_then(|c| c.import_name.as_deref());
+        let file = match import_name {
+            Some(import_name) => {
+                let import = analysis.imports.iter().find(|i| i.name == import_name)?;
+                PathBuf::from(import.path.as_deref()?)
+            }
+            None => module.to_path_buf(),
+        };
+        let return_type = self.store.get(&file)?.function(name)?.return_type.as_deref()?;
+        Some((file, return_type))
+    }
+
+    /// Clase del workspace que nombra `name` visto desde `from`. Un nombre calificado (`models.Product`)
+    /// se busca primero en el módulo que `from` importa con ese nombre; si no, se busca por su último segmento.
+    pub fn resolve_class(&self, from: &Path, name: &str) -> Option<SymbolId> {
+        let (qualifier, class_name) = match name.rsplit_once('.') {
+            Some((qualifier, class_name)) => (Some(qualifier), class_name),
+            None => (None, name),
+        };
+        if let Some(qualifier) = qualifier
+            && let Some(path) = self
+                .store
+                .get(from)
+                .and_then(|a| a.imports.iter().find(|i| i.name == qualifier))
+                .and_then(|i| i.path.as_deref())
+            && self.store.get(Path::new(path)).is_some_and(|a| a.class(class_name).is_some())
+        {
+            return Some(SymbolId::class(path, class_name));
+        }
+        let file = self.find_class_file(from, class_name)?;
+        Some(SymbolId::class(&file, class_name))
+    }
+
+    /// Clases del workspace que admite la anotación `annotation` escrita en `from`
+    /// (una por alternativa si es una unión).
+    pub fn annotation_classes(&self, from: &Path, annotation: &str) -> Vec<SymbolId> {
+        self.resolve_classes(from, Annotation::parse(annotation).classes())
+    }
+
+    /// Clases de los elementos que produce iterar un valor anotado con `annotation`
+    /// (`list[Product]` → `Product`).
+    pub fn element_classes(&self, from: &Path, annotation: &str) -> Vec<SymbolId> {
+        self.resolve_classes(from, Annotation::parse(annotation).element_classes())
+    }
+
+    fn resolve_classes(&self, from: &Path, names: Vec<String>) -> Vec<SymbolId> {
+        let mut classes = vec![];
+        for class in names.iter().filter_map(|name| self.resolve_class(from, name)) {
+            if !classes.contains(&class) {
+                classes.push(class);
+            }
+        }
+        classes
+    }
+
+    /// Módulos cuyas clases dependen de las definidas en `changed`: los que definen subclases suyas
+    /// (directas o no, cambia su MRO) y los que declaran atributos tipados con alguna de ellas.
+    /// Los propios `changed` no se incluyen.
+    pub fn class_dependents(&self, changed: &[PathBuf]) -> Vec<PathBuf> {
+        let mut affected: Vec<SymbolId> = changed
+            .iter()
+            .filter_map(|path| Some((path.to_string_lossy(), self.store.get(path)?)))
+            .flat_map(|(module, analysis)| {
+                analysis.classes.iter().map(move |def| SymbolId::class(&module, &def.name))
+            })
+            .collect();
+
+        // Subclases transitivas: se agregan al final y también se recorren
+        let inheritance = self.inheritance();
+        let mut next = 0;
+        while next < affected.len() {
+            let base = affected[next].clone();
+            next += 1;
+            for relation in inheritance.iter().filter(|r| r.base == base) {
+                if !affected.contains(&relation.subclass) {
+                    affected.push(relation.subclass.clone());
+                }
+            }
+        }
+
+        let mut modules: Vec<PathBuf> = affected.iter().map(|class| PathBuf::from(&class.module)).collect();
+        for path in &self.paths {
+            let module = path.to_string_lossy();
+            for def in &self.store[*path].classes {
+                let class = SymbolId::class(&module, &def.name);
+                // Atributos del cuerpo de la clase y `self.<atributo>` asignados en `__init__`
+                let init_attributes = def
+                    .method("__init__")
+                    .into_iter()
+                    .flat_map(|init| &init.local_variables)
+                    .filter_map(|v| v.name.strip_prefix("self."));
+                let mut attributes = def.attributes.iter().map(|a| a.name.as_str()).chain(init_attributes);
+                if attributes.any(|attribute| {
+                    self.declared_attribute_types(&class, attribute)
+                        .is_some_and(|types| types.iter().any(|t| affected.contains(t)))
+                }) {
+                    modules.push((*path).clone());
+                }
+            }
+        }
+        modules.sort();
+        modules.dedup();
+        modules.retain(|path| !changed.contains(path));
+        modules
+    }
+
+    /// Todas las relaciones de herencia entre clases del workspace, en orden estable.
+    pub fn inheritance(&self) -> Vec<Inheritance> {
+        let mut relations = vec![];
+        for path in &self.paths {
+            let module = path.to_string_lossy();
+            for def in &self.store[*path].classes {
+                let subclass = SymbolId::class(&module, &def.name);
+                for base in self.bases(&subclass) {
+                    relations.push(Inheritance {
+                        subclass: subclass.clone(),
+                        base,
+                        line: def.line.unwrap_or(1),
+                        start_col: def.name_start_col,
+                        end_col: def.name_end_col.max(def.name_start_col),
+                    });
+                }
+            }
+        }
+        relations
+    }
+}
+
+fn non_empty(classes: Vec<SymbolId>) -> Option<Vec<SymbolId>> {
+    (!classes.is_empty()).then_some(classes)
+}
+
+/// Merge de la linearización C3. Retorna `None` si no existe un orden consistente.
+fn c3_merge(mut sequences: Vec<Vec<SymbolId>>) -> Option<Vec<SymbolId>> {
+    let mut result = vec![];
+    loop {
+        sequences.retain(|s| !s.is_empty());
+        if sequences.is_empty() {
+            return Some(result);
+        }
+        // Primer head que no aparece en la cola de ninguna otra secuencia
+        let head = sequences
+            .iter()
+            .map(|s| &s[0])
+            .find(|candidate| !sequences.iter().any(|s| s[1..].contains(candidate)))?
+            .clone();
+        for sequence in &mut sequences {
+            if sequence[0] == head {
+                sequence.remove(0);
+            }
+        }
+        result.push(head);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{ClassAttribute, Import, LocalVariable};
+
+    fn class(name: &str, bases: &[&str], methods: &[&str]) -> ClassDef {
+        ClassDef {
+            name: name.to_string(),
+            bases: bases.iter().map(|b| b.to_string()).collect(),
+            methods: methods
+                .iter()
+                .map(|m| FunctionDef { name: m.to_string(), line: 1, ..Default::default() })
+                .collect(),
+            line: Some(1),
+            ..Default::default()
+        }
+    }
+
+    /// Diamante clásico: `D(B, C)`, `B(A)`, `C(A)`; `A` en otro módulo importado con nombre calificado.
+    fn diamond() -> HashMap<PathBuf, FileAnalysis> {
+        let base = FileAnalysis {
+            classes: vec![class("A", &[], &["save", "describe"])],
+            ..Default::default()
+        };
+        let shop = FileAnalysis {
+            imports: vec![Import { name: "base".to_string(), path: Some("/ws/base.py".to_string()) }],
+            classes: vec![
+                class("B", &["base.A"], &[]),
+                class("C", &["base.A"], &["describe"]),
+                class("D", &["B", "C"], &[]),
+            ],
+            ..Default::default()
+        };
+        HashMap::from([
+            (PathBuf::from("/ws/base.py"), base),
+            (PathBuf::from("/ws/shop.py"), shop),
+        ])
+    }
+
+    #[test]
+    fn mro_follows_c3_linearization() {
+        let store = diamond();
+        let hierarchy = ClassHierarchy::new(&store);
+        let names: Vec<String> = hierarchy
+            .mro(&SymbolId::class("/ws/shop.py", "D"))
+            .into_iter()
+            .map(|c| c.name)
+            .collect();
+        assert_eq!(names, vec!["D", "B", "C", "A"]);
+    }
+
+    #[test]
+    fn find_method_owner_walks_the_mro() {
+        let store = diamond();
+        let hierarchy = ClassHierarchy::new(&store);
+        let d = SymbolId::class("/ws/shop.py", "D");
+
+        // `C.describe` gana sobre `A.describe` porque C va antes que A en el MRO de D
+        assert_eq!(hierarchy.find_method_owner(&d, "describe"), Some(SymbolId::class("/ws/shop.py", "C")));
+        assert_eq!(hierarchy.find_method_owner(&d, "save"), Some(SymbolId::class("/ws/base.py", "A")));
+        assert_eq!(hierarchy.find_method_owner(&d, "missing"), None);
+        assert_eq!(hierarchy.inheritance().len(), 4);
+    }
+
+    #[test]
+    fn attribute_type_is_inherited_from_a_base_init() {
+        let mut store = diamond();
+        let base = store.get_mut(Path::new("/ws/base.py")).unwrap();
+        base.classes.push(class("Cache", &[], &["get"]));
+        base.functions.push(FunctionDef {
+            name: "make_cache".to_string(),
+            return_type: Some("Cache".to_string()),
+            line: 1,
+            ..Default::default()
+        });
+        base.classes[0].methods.push(FunctionDef {
+            name: "__init__".to_string(),
+            line: 1,
+            local_variables: vec![LocalVariable {
+                name: "self.cache".to_string(),
+                assigned_from: Some("make_cache".to_string()),
+                ..Default::default()
+            }],
+            ..Default::default()
+        });
+        let hierarchy = ClassHierarchy::new(&store);
+        let d = SymbolId::class("/ws/shop.py", "D");
+
+        // `self.cache = make_cache()` en `A.__init__`: D lo hereda por su MRO
+        assert_eq!(hierarchy.attribute_types(&d, "cache"), vec![SymbolId::class("/ws/base.py", "Cache")]);
+        assert!(hierarchy.attribute_types(&d, "missing").is_empty());
+    }
+
+    #[test]
+    fn class_dependents_cover_subclasses_and_typed_attributes() {
+        let mut store = diamond();
+        // `E(D)` en otro módulo y un servicio con `repo: D`, que sólo importan shop.py
+        let shop_import = Import { name: "shop".to_string(), path: Some("/ws/shop.py".to_string()) };
+        let mut service = class("Service", &[], &[]);
+        service.attributes.push(ClassAttribute { name: "repo".to_string(), annotation: Some("shop.D".to_string()) });
+        store.insert(
+            PathBuf::from("/ws/extra.py"),
+            FileAnalysis { imports: vec![shop_import.clone()], classes: vec![class("E", &["shop.D"], &[])], ..Default::default() },
+        );
+        store.insert(
+            PathBuf::from("/ws/service.py"),
+            FileAnalysis { imports: vec![shop_import], classes: vec![service], ..Default::default() },
+        );
+        store.insert(PathBuf::from("/ws/other.py"), FileAnalysis { classes: vec![class("F", &[], &[])], ..Default::default() });
+        let hierarchy = ClassHierarchy::new(&store);
+
+        // Un cambio en `A` (base.py) alcanza a sus subclases, a la subclase de una subclase y al atributo tipado
+        assert_eq!(
+            hierarchy.class_dependents(&[PathBuf::from("/ws/base.py")]),
+            vec![PathBuf::from("/ws/extra.py"), PathBuf::from("/ws/service.py"), PathBuf::from("/ws/shop.py")]
+        );
+        assert!(hierarchy.class_dependents(&[PathBuf::from("/ws/other.py")]).is_empty());
+    }
+
+    #[test]
+    fn cyclic_hierarchies_do_not_loop() {
+        let store = HashMap::from([(
+            PathBuf::from("/ws/a.py"),
+            FileAnalysis {
+                classes: vec![class("X", &["Y"], &[]), class("Y", &["X"], &["run"])],
+                ..Default::default()
+            },
+        )]);
+        let hierarchy = ClassHierarchy::new(&store);
+        let x = SymbolId::class("/ws/a.py", "X");
+        assert_eq!(hierarchy.find_method_owner(&x, "run"), Some(SymbolId::class("/ws/a.py", "Y")));
+    }
+
+    #[test]
+    fn find_class_file_prefers_imports_then_own_file_then_workspace() {
+        let mut store = diamond();
+        store.insert(
+            PathBuf::from("/ws/a_other.py"),
+            FileAnalysis { classes: vec![class("A", &[], &[]), class("C", &[], &[])], ..Default::default() },
+        );
+        let hierarchy = ClassHierarchy::new(&store);
+        let shop = Path::new("/ws/shop.py");
+        let base = Path::new("/ws/base.py");
+
+        // shop.py importa base.py, que define A (aunque a_other.py va antes en orden de path)
+        assert_eq!(hierarchy.find_class_file(shop, "A").as_deref(), Some("/ws/base.py"));
+        // C está en el propio archivo
+        assert_eq!(hierarchy.find_class_file(shop, "C").as_deref(), Some("/ws/shop.py"));
+        // base.py no importa nada: el primero del workspace que define C
+        assert_eq!(hierarchy.find_class_file(base, "C").as_deref(), Some("/ws/a_other.py"));
+        assert_eq!(hierarchy.find_class_file(shop, "Missing"), None);
+
+        // El MRO memoizado es el mismo en consultas repetidas
+        let d = SymbolId::class("/ws/shop.py", "D");
+        assert_eq!(hierarchy.mro(&d), hierarchy.mro(&d));
+    }
+}
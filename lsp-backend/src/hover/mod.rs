@@ -0,0 +1,132 @@
+use crate::FunctionsInFiles;
+use crate::model::{FileAnalysis, FunctionDef};
+
+const DOCSTRING_EXCERPT_MAX_CHARS: usize = 300;
+
+/// Busca en el análisis de un archivo la función o método que corresponde a `definition`.
+pub fn find_function<'a>(analysis: &'a FileAnalysis, definition: &FunctionsInFiles) -> Option<&'a FunctionDef> {
+    match &definition.symbol.class_name {
+        Some(class_name) => analysis.class(class_name)?.method(&definition.symbol.name),
+        None => analysis.function(&definition.symbol.name),
+    }
+}
+
+/// Arma la firma Python de una función a partir de sus `parameters` y `return_type`.
+/// Ej: `def add_product(name: str, price: float = 0.0) -> Product`
+pub fn render_signature(name: &str, func: &FunctionDef) -> String {
+    let params: Vec<String> = func
+        .parameters
+        .iter()
+        .map(|p| {
+            let mut rendered = p.name.clone();
+            if let Some(ty) = &p.param_type {
+                rendered.push_str(&format!(": {ty}"));
+            }
+            if let Some(default) = &p.default_value {
+                rendered.push_str(&format!(" = {default}"));
+            }
+            rendered
+        })
+        .collect();
+
+    let mut signature = format!("def {}({})", name, params.join(", "));
+    if let Some(rt) = &func.return_type {
+        signature.push_str(&format!(" -> {rt}"));
+    }
+    signature
+}
+
+/// Retorna el primer párrafo del docstring (si el analizador lo provee), recortado a un largo razonable.
+pub fn docstring_excerpt(func: &FunctionDef) -> Option<String> {
+    let doc = func.docstring.as_deref()?.trim();
+    let first_paragraph = doc.split("\n\n").next()?.trim();
+    if first_paragraph.is_empty() {
+        return None;
+    }
+    if first_paragraph.chars().count() <= DOCSTRING_EXCERPT_MAX_CHARS {
+        return Some(first_paragraph.to_string());
+    }
+    let truncated: String = first_paragraph.chars().take(DOCSTRING_EXCERPT_MAX_CHARS).collect();
+    Some(format!("{}…", truncated.trim_end()))
+}
+
+/// Arma el contenido markdown del hover: firma, archivo que la define, docstring y conteo de llamadas.
+pub fn hover_markdown(
+    definition: &FunctionsInFiles,
+    func: &FunctionDef,
+    relative_file: &str,
+    incoming: usize,
+    outgoing: usize,
+) -> String {
+    let mut sections = vec![format!(
+        "```python\n{}\n```",
+        render_signature(&definition.symbol.name, func)
+    )];
+
+    let defined_in = match &definition.symbol.class_name {
+        Some(class_name) => format!("Defined in `{relative_file}` (class `{class_name}`)"),
+        None => format!("Defined in `{relative_file}`"),
+    };
+    sections.push(defined_in);
+
+    if let Some(doc) = docstring_excerpt(func) {
+        sections.push(doc);
+    }
+
+    sections.push(format!(
+        "---\n{incoming} incoming call{} · {outgoing} outgoing call{}",
+        if incoming == 1 { "" } else { "s" },
+        if outgoing == 1 { "" } else { "s" },
+    ));
+
+    sections.join("\n\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::symbol::SymbolId;
+    use serde_json::json;
+
+    #[test]
+    fn render_signature_includes_types_defaults_and_return() {
+        let func: FunctionDef = serde_json::from_value(json!({
+            "name": "add_product",
+            "parameters": [
+                { "name": "name", "param_type": "str", "default_value": null },
+                { "name": "price", "param_type": "float", "default_value": "0.0" },
+                "tags"
+            ],
+            "return_type": "Product",
+            "function_calls": [],
+            "line": 1, "name_start_col": 4, "name_end_col": 15
+        }))
+        .unwrap();
+
+        assert_eq!(
+            render_signature("add_product", &func),
+            "def add_product(name: str, price: float = 0.0, tags) -> Product"
+        );
+    }
+
+    #[test]
+    fn find_function_looks_into_class_methods() {
+        let analysis: FileAnalysis = serde_json::from_value(json!({
+            "imports": [],
+            "functions": [{ "name": "save", "return_type": "None", "function_calls": [],
+                            "line": 1, "name_start_col": 4, "name_end_col": 8 }],
+            "classes": [{ "name": "Order", "methods": [{ "name": "save", "return_type": "bool", "function_calls": [],
+                                                          "line": 4, "name_start_col": 8, "name_end_col": 12 }] }]
+        }))
+        .unwrap();
+        let definition = FunctionsInFiles {
+            symbol: SymbolId::method("/ws/order.py", "Order", "save"),
+            line: 4,
+            name_start_col: 8,
+            name_end_col: 12,
+        };
+
+        let method = find_function(&analysis, &definition).unwrap();
+        assert_eq!(method.return_type.as_deref(), Some("bool"));
+    }
+}
@@ -9,60 +9,140 @@ use tree_sitter_test::run_analysis;
 use blake3; // Hash para los paths
 use serde_json::Value;
 use std::borrow::Cow;
-use std::collections::HashSet;
-use std::u32;
+use std::time::Duration;
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
     path::{Path, PathBuf},
 };
 use tokio::fs;
 use tokio::io::AsyncWriteExt;
-use tokio::sync::{RwLock, RwLockReadGuard};
-
+use futures::StreamExt;
+use std::ops::Deref;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::sync::{Mutex, Notify, RwLock, RwLockReadGuard};
+
+use crate::deps::DependencyIndex;
+use crate::graph::{EdgeKind, GraphDelta, GraphEdge, GraphState};
+use crate::hierarchy::ClassHierarchy;
+use crate::model::{ClassDef, FileAnalysis, FunctionCall, FunctionDef, Import};
+use crate::symbol::SymbolId;
+use crate::progress::WorkProgress;
+use crate::quarantine::{Quarantine, QuarantinedFile};
 use crate::utils::FileWarn;
 
+mod annotation;
+mod deps;
+mod diagnostics;
+mod graph;
+mod hierarchy;
+mod hover;
+mod model;
+mod moves;
+mod navigation;
+mod progress;
+mod quarantine;
+mod symbol;
+mod symbols;
 mod utils;
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
-struct FunctionData {
-    name: String,
-    parameters: Vec<Value>,
-    return_type: Option<Value>,
-    function_calls: Vec<Value>, // Value = { "name": String, "import_name": Option<String>}
-}
 #[derive(Serialize, Deserialize, Clone, Debug)]
 struct LspFileMessage {
     file_name: String,
-    classes: Vec<Value>,
-    functions: Vec<FunctionData>,
-    imports: Vec<Value>, // Value = { "name": String, "path": Option<String>}
+    classes: Vec<ClassDef>,
+    functions: Vec<FunctionDef>,
+    imports: Vec<Import>,
 }
 #[derive(Serialize, Deserialize, Clone, Debug)]
 struct Connections {
-    file_src: String,
+    // Archivo y posición del call site
     file_use: String,
     line: i64,
     start_col: usize,
     end_col: usize,
-    function: String,
+    // Símbolo llamado (su `module` es el archivo que lo define)
+    target: SymbolId,
+    // Función o método desde el que se hace la llamada
+    caller: SymbolId,
+    // El archivo destino fue borrado: la llamada ya no resuelve
+    #[serde(default)]
+    dangling: bool,
+    // Llamada común o instanciación de una clase (`Product(...)`)
+    #[serde(default)]
+    kind: EdgeKind,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 struct FunctionsInFiles {
-    file_src: String,
-    function: String,
+    symbol: SymbolId,
     line: i64,
     name_start_col: usize,
     name_end_col: usize,
 }
 
-#[derive(Debug)]
+/// Buffer abierto en el editor (sincronizado con `TextDocumentSyncKind::FULL`).
+#[derive(Debug, Clone)]
+struct OpenDocument {
+    text: String,
+    version: i32,
+}
+
+/// Cola de eventos de archivos pendientes de re-analizar (coalescidos por path).
+#[derive(Debug, Default)]
+struct AnalysisQueue {
+    pending: HashMap<PathBuf, FileChangeType>,
+    // Paths guardados desde el editor: tras re-analizarlos se avisa de cambios de firma
+    saved: HashSet<PathBuf>,
+    // Renames hechos desde el editor (`didRenameFiles`): se migran en el próximo lote
+    renames: Vec<(PathBuf, PathBuf)>,
+    // Eventos que el watcher va a mandar por renames ya migrados (DELETED del path viejo, CREATED
+    // del nuevo): se descartan una vez cada uno en vez de re-analizar u olvidar el archivo
+    expected_events: HashMap<PathBuf, FileChangeType>,
+}
+
+/// Servidor LSP. Es un handle barato de clonar sobre el estado compartido, así las tareas de
+/// fondo (el worker de re-análisis) pueden conservarlo más allá de un handler.
+#[derive(Debug, Clone)]
 struct Backend {
+    state: Arc<BackendState>,
+}
+
+impl Deref for Backend {
+    type Target = BackendState;
+
+    fn deref(&self) -> &BackendState {
+        &self.state
+    }
+}
+
+#[derive(Debug)]
+struct BackendState {
     client: Client,
     // Estado global: resultados por archivo (en memoria)
-    store: RwLock<HashMap<PathBuf, Value>>,
+    store: RwLock<HashMap<PathBuf, FileAnalysis>>,
     connections: RwLock<Vec<Connections>>,
     functions_in_file: RwLock<Vec<FunctionsInFiles>>,
+    // Índice de imports entre archivos, para re-resolver dependientes cuando cambia un archivo
+    dependency_index: RwLock<DependencyIndex>,
+    // Hash del contenido analizado de cada archivo (para detectar moves: delete + create iguales)
+    content_hashes: RwLock<HashMap<PathBuf, String>>,
+    // Módulos borrados del workspace: las llamadas hacia ellos se resuelven como `dangling`
+    deleted_modules: RwLock<HashSet<PathBuf>>,
+    // Buffers abiertos en el editor: se analizan en lugar del contenido en disco
+    open_documents: RwLock<HashMap<PathBuf, OpenDocument>>,
+    // Cola de re-análisis: los handlers encolan y despiertan al worker, que hace el debounce.
+    // El lock serializa el procesamiento de lotes
+    analysis_queue: Mutex<AnalysisQueue>,
+    analysis_signal: Notify,
+    batch_lock: Mutex<()>,
+    // Cantidad de archivos analizados en paralelo (`initializationOptions.analysisWorkers`)
+    analysis_workers: RwLock<usize>,
+    // Se incrementa al cancelar: los análisis que arrancaron con otro valor se detienen
+    analysis_epoch: AtomicU64,
+    // Último grafo enviado al cliente, para mandar sólo deltas versionados
+    graph_state: Mutex<GraphState<GraphEdge>>,
+    // Archivos cuyo análisis falló: se reportan como diagnóstico y en `lsp-server/status`
+    quarantine: RwLock<Quarantine>,
     // Raíz del workspace (la resolvemos en initialize)
     workspace_root: RwLock<PathBuf>,
     // Carpetas a ignorar (cargadas desde .lspignore)
@@ -75,11 +155,15 @@ struct CustomData {
     summary: String,
 }
 struct ProcessedJson;
+struct GraphDeltaNotification;
 struct ShowFilesToChange;
 
+/// Grafo completo en una revisión: archivos y llamadas resueltas (paths relativos al workspace).
 #[derive(Serialize, Debug, Deserialize)]
 struct ProcessedJsonPayload {
+    revision: u64,
     files: Vec<LspFileMessage>,
+    edges: Vec<GraphEdge>,
 }
 
 #[derive(Serialize, Debug, Deserialize)]
@@ -92,6 +176,11 @@ impl Notification for ProcessedJson {
     const METHOD: &'static str = "lsp-server/processedJson";
 }
 
+impl Notification for GraphDeltaNotification {
+    type Params = GraphDelta<GraphEdge>;
+    const METHOD: &'static str = "lsp-server/graphDelta";
+}
+
 impl Notification for ShowFilesToChange {
     type Params = ShowFilesToChangePayload;
     const METHOD: &'static str = "lsp-server/showFilesToChange";
@@ -102,6 +191,17 @@ struct RenameRequest {
     file_path: String,
     old_name: String,
     new_name: String,
+    // ID del nodo en el grafo (`fn::`/`mth::`); distingue métodos homónimos de distintas clases
+    #[serde(default)]
+    symbol_id: Option<String>,
+}
+
+/// Estado del servidor para `lsp-server/status`.
+#[derive(Serialize, Deserialize, Debug)]
+struct ServerStatus {
+    analyzed_files: usize,
+    connections: usize,
+    quarantined: Vec<QuarantinedFile>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -111,6 +211,10 @@ struct RenameResult {
     files_edited: Option<Vec<String>>,
 }
 
+/// Tiempo sin eventos nuevos (cambios en buffers o en disco) antes de procesar la cola de re-análisis.
+const ANALYSIS_DEBOUNCE: Duration = Duration::from_millis(400);
+
+
 // Helpers para manejo de paths
 
 /// Devuelve el directorio de caché (`<workspace>/.lsp-analysis/files`) para el workspace dado.
@@ -139,6 +243,67 @@ fn resolve_workspace_root(params: &InitializeParams) -> Option<PathBuf> {
     None
 }
 
+/// Devuelve el directorio donde se escriben los buffers sin guardar para analizarlos
+/// (`<workspace>/.lsp-analysis/buffers`).
+fn buffers_root_for_workspace(workspace_root: &Path) -> PathBuf {
+    workspace_root.join(".lsp-analysis").join("buffers")
+}
+
+/// Lleva al árbol real los imports de un análisis hecho sobre el archivo sombra de un buffer
+/// (bajo `buffers_root`): los paths resueltos dentro del espejo pasan a apuntar al archivo del
+/// workspace, y los imports relativos que desde el espejo no resolvieron conservan el path que
+/// tenían en `previous` (el análisis anterior del archivo) para el mismo nombre.
+fn overlay_imports(
+    analysis: &mut FileAnalysis,
+    buffers_root: &Path,
+    root: &Path,
+    previous: Option<&FileAnalysis>,
+) {
+    for import in &mut analysis.imports {
+        match import.path.as_deref() {
+            Some(path) => {
+                if let Some(real) = moves::remap_path(Path::new(path), buffers_root, root) {
+                    import.path = Some(real.to_string_lossy().into_owned());
+                }
+            }
+            None => {
+                import.path = previous
+                    .and_then(|p| p.imports.iter().find(|i| i.name == import.name))
+                    .and_then(|i| i.path.clone());
+            }
+        }
+    }
+}
+
+/// Cantidad de workers de análisis: `analysisWorkers` de las `initializationOptions` si es un
+/// entero positivo; si no, el paralelismo disponible en la máquina.
+fn analysis_workers_from_options(options: Option<&Value>) -> usize {
+    options
+        .and_then(|o| o.get("analysisWorkers"))
+        .and_then(|w| w.as_u64())
+        .filter(|w| *w > 0)
+        .map(|w| w as usize)
+        .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |n| n.get()))
+}
+
+/// Indexa los archivos del grafo por `file_name`, serializados para poder compararlos entre revisiones.
+fn files_by_name(files: &[LspFileMessage]) -> BTreeMap<String, Value> {
+    files
+        .iter()
+        .map(|f| (f.file_name.clone(), serde_json::to_value(f).unwrap_or(Value::Null)))
+        .collect()
+}
+
+/// Retorna `true` si `path` es un archivo fuente que el analizador sabe procesar.
+fn is_analyzable(path: &Path) -> bool {
+    matches!(path.extension().and_then(|e| e.to_str()), Some("py") | Some("js"))
+}
+
+/// Retorna `true` si `path` es un archivo interno del servidor (caché o buffers en `.lsp-analysis`).
+fn is_internal_path(path: &Path, workspace_root: &Path) -> bool {
+    path.starts_with(workspace_root.join(".lsp-analysis"))
+}
+
 /// Crea el directorio `dir` y todos sus padres si no existen.
 async fn ensure_dirs(dir: &Path) -> std::io::Result<()> {
     fs::create_dir_all(dir).await
@@ -235,6 +400,27 @@ async fn cleanup_orphan_entries_in(cache_dir: &Path) {
     }
 }
 
+/// Mueve la entrada de caché de `old` a `new` dentro de `cache_dir`, actualizando su `original_path`.
+/// Si la entrada no existe o no es JSON válido no hace nada (el archivo se re-analizará).
+async fn migrate_cache_entry(cache_dir: &Path, old: &Path, new: &Path) {
+    let old_entry = cache_dir.join(format!("{}.json", hash_path(old)));
+    let Ok(raw) = fs::read_to_string(&old_entry).await else {
+        return;
+    };
+    let _ = fs::remove_file(&old_entry).await;
+    let Ok(mut cached) = serde_json::from_str::<Value>(&raw) else {
+        return;
+    };
+    if let Some(obj) = cached.as_object_mut() {
+        obj.insert(
+            "original_path".to_string(),
+            Value::String(new.to_string_lossy().into_owned()),
+        );
+    }
+    let new_entry = cache_dir.join(format!("{}.json", hash_path(new)));
+    let _ = write_json_atomic(&new_entry, &cached).await;
+}
+
 /// Lee `.lspignore` en la raíz del workspace y retorna la lista de paths a ignorar.
 /// Las líneas vacías y los comentarios (`#`) se descartan. Retorna vacío si el archivo no existe.
 async fn load_ignore_list(workspace_root: &Path) -> Vec<PathBuf> {
@@ -259,63 +445,440 @@ fn is_ignored(path: &Path, ignored_folders: &[PathBuf]) -> bool {
 /// Convierte el store en memoria en la lista de `LspFileMessage` lista para enviar al frontend.
 /// Los paths de archivo se relativizan respecto a `root`.
 fn format_for_lsp_message(
-    data: RwLockReadGuard<'_, HashMap<PathBuf, Value>>,
+    data: RwLockReadGuard<'_, HashMap<PathBuf, FileAnalysis>>,
     root: PathBuf
 ) -> Vec<LspFileMessage> {
-    /// Strips `root` from `abs_path` and returns a clean relative path string.
-    fn relativize(abs_path: &Path, root: &Path) -> String {
-        abs_path
-            .strip_prefix(root)
-            .unwrap_or(abs_path)
-            .to_string_lossy()
-            .into_owned()
-    }
-
     data.iter()
-        .filter_map(|(path, value)| {
-            // aseguramos que el Value tenga las keys esperadas
-            let classes = value.get("classes")?.clone();
-            let functions = value.get("functions")?.clone();
-            let imports_raw = value.get("imports")?.as_array()?.clone();
-
-            // intentamos deserializar las funciones (podrían ser objetos)
-            let functions: Vec<FunctionData> = serde_json::from_value(functions).ok()?;
-
-            // Relativizamos el path del archivo usando Path::strip_prefix (más robusto que str)
-            let file_name = relativize(path, &root);
+        .map(|(path, analysis)| {
+            let file_name = graph::relativize(&path.to_string_lossy(), &root);
 
             // Relativizamos también los paths dentro de cada import
-            let imports = imports_raw
-                .into_iter()
-                .map(|mut import| {
-                    if let Some(path_str) = import.get("path").and_then(|p| p.as_str()) {
-                        let relative = relativize(Path::new(path_str), &root);
-                        if let Some(obj) = import.as_object_mut() {
-                            obj.insert("path".to_string(), Value::String(relative));
-                        }
-                    }
-                    import
+            let imports = analysis
+                .imports
+                .iter()
+                .map(|import| Import {
+                    name: import.name.clone(),
+                    path: import.path.as_deref().map(|p| graph::relativize(p, &root)),
                 })
-                .collect::<Vec<_>>();
-            let path_string = path.to_str().unwrap_or("");
-            let root_str = root.to_str().unwrap_or("");
+                .collect();
 
-            let file_path = path_string.strip_prefix(root_str)
-              .unwrap_or(path_string)
-              .trim_start_matches('/')
-              .to_string();
-
-            Some(LspFileMessage {
+            LspFileMessage {
                 file_name,
-                classes: classes.as_array().cloned().unwrap_or_default(),
-                functions,
+                classes: analysis.classes.clone(),
+                functions: analysis.functions.clone(),
                 imports,
-            })
+            }
         })
         .collect()
 }
 
+/// Resuelve las llamadas de un archivo analizado contra `store_snapshot` y retorna sus `Connections`.
+/// Cubre funciones importadas, métodos sobre variables/parámetros tipados, cadenas y llamadas locales.
+/// `hierarchy` es la jerarquía de clases de ese mismo snapshot (se construye una vez por lote).
+/// `deleted_modules` son los módulos borrados: las llamadas hacia ellos quedan `dangling`.
+fn resolve_connections(
+    original_path: &Path,
+    analysis: &FileAnalysis,
+    store_snapshot: &HashMap<PathBuf, FileAnalysis>,
+    hierarchy: &ClassHierarchy,
+    deleted_modules: &HashSet<PathBuf>,
+) -> Vec<Connections> {
+    let path_string = original_path.to_string_lossy().to_string();
+    let mut all_connections: Vec<Connections> = vec![];
+
+    // Los imports que el analizador no pudo resolver (sin `path`) no generan connections
+    let imports_hashmap: HashMap<String, String> = analysis
+        .imports
+        .iter()
+        .filter_map(|import| Some((import.name.clone(), import.path.clone()?)))
+        .collect();
+
+    // Helper closure: clases del workspace que admite una anotación escrita en este archivo
+    // (una por alternativa de una unión; los nombres calificados se buscan en el módulo importado)
+    let annotation_classes = |annotation: &str| -> Vec<SymbolId> {
+        hierarchy.annotation_classes(original_path, annotation)
+    };
+
+    // Helper closure: dado un import_module y un function name, devuelve las clases que retorna
+    // la función importada (su return_type, resuelto en el archivo que la define)
+    let imported_return_classes = |import_module: &str, func_name: &str| -> Vec<SymbolId> {
+        let Some(file_path) = imports_hashmap.get(import_module) else {
+            return vec![];
+        };
+        store_snapshot
+            .get(Path::new(file_path))
+            .and_then(|a| a.function(func_name)?.return_type.as_deref())
+            .map(|rt| hierarchy.annotation_classes(Path::new(file_path), rt))
+            .unwrap_or_default()
+    };
+
+    // Helper closure: dada una clase (archivo y nombre) y un método, devuelve la clase que lo define:
+    // la propia o, si no lo define, la primera de su MRO que lo tenga.
+    let find_method_owner = |class_file: &str, class_name: &str, method_name: &str| -> Option<(String, String)> {
+        let owner = hierarchy.find_method_owner(&SymbolId::class(class_file, class_name), method_name)?;
+        Some((owner.module, owner.name))
+    };
+
+    // Helper closure: símbolo del método `method_name` sobre instancias de una clase. Si ni la clase
+    // ni sus bases lo definen, no hay método al que apuntar.
+    let resolve_method = |class_file: &str, class_name: &str, method_name: &str| -> Option<SymbolId> {
+        let (owner_file, owner) = find_method_owner(class_file, class_name, method_name)?;
+        Some(SymbolId::method(&owner_file, &owner, method_name))
+    };
+
+    // Helper closure: dado el archivo que define una clase, su nombre y el nombre de un método,
+    // devuelve las clases que retorna ese método, heredado o no (para resolver cadenas de N niveles).
+    let method_return_classes = |class_file: &str, class_name: &str, method_name: &str| -> Vec<SymbolId> {
+        let Some((owner_file, owner)) = find_method_owner(class_file, class_name, method_name) else {
+            return vec![];
+        };
+        store_snapshot
+            .get(Path::new(&owner_file))
+            .and_then(|a| a.class(&owner)?.method(method_name)?.return_type.as_deref())
+            .map(|rt| hierarchy.annotation_classes(Path::new(&owner_file), rt))
+            .unwrap_or_default()
+    };
+
+    let process_function_calls = |
+        function: &FunctionDef,
+        enclosing_class: Option<&ClassDef>,
+        caller: &SymbolId,
+        path_string: &str,
+        imports_hashmap: &HashMap<String, String>,
+    | -> Vec<Connections> {
+        let function_calls: &[FunctionCall] = &function.function_calls;
+        let mut new_connections = vec![];
+
+        // `self.m()` / `cls.m()` dentro de un método. El analizador reporta el receptor como
+        // `object_name` o, en algunas versiones, como un `import_name` que no es ningún import.
+        let self_call_class = |fc: &FunctionCall| -> Option<&ClassDef> {
+            if fc.chain_source_fn.is_some() {
+                return None;
+            }
+            let receiver = fc
+                .object_name
+                .as_deref()
+                .or(fc.import_name.as_deref().filter(|m| !imports_hashmap.contains_key(*m)))?;
+            let shadowed = function.local_variable(receiver).is_some();
+            enclosing_class.filter(|_| matches!(receiver, "self" | "cls") && !shadowed)
+        };
+
+        // `Product(...)` / `shop.Order(...)`: instanciación de una clase del workspace, importada
+        // (el analizador la reporta con `import_name`, igual que una función) o del mismo archivo.
+        let constructed_class = |fc: &FunctionCall| -> Option<SymbolId> {
+            if fc.object_name.is_some() || fc.chain_source_fn.is_some() {
+                return None;
+            }
+            match fc.import_name.as_deref() {
+                Some(module) => {
+                    let file = imports_hashmap.get(module)?;
+                    store_snapshot.get(Path::new(file))?.class(&fc.name)?;
+                    Some(SymbolId::class(file, &fc.name))
+                }
+                None => {
+                    analysis.class(&fc.name)?;
+                    Some(SymbolId::class(path_string, &fc.name))
+                }
+            }
+        };
+
+        // ── Pre-pass: construir dos mapas para habilitar resolución de cadenas N-profundas
+        //
+        // call_sources: call_name → archivo donde ese método/función está definido
+        //               (se usa como file_src en la Connection)
+        // call_contexts: call_name → clases que retorna (más de una si el return_type es una unión)
+        //               (se usa para resolver el SIGUIENTE eslabón de la cadena)
+        let mut call_sources: HashMap<String, String>          = HashMap::new();
+        let mut call_contexts: HashMap<String, Vec<SymbolId>>  = HashMap::new();
+
+        for fc in function_calls {
+            if let Some(class) = self_call_class(fc) {
+                // Método de la propia clase (o heredado): puede ser el primer eslabón de una cadena
+                if let Some((owner_file, owner)) = find_method_owner(path_string, &class.name, &fc.name) {
+                    let returned = method_return_classes(&owner_file, &owner, &fc.name);
+                    if !returned.is_empty() {
+                        call_contexts.insert(fc.name.clone(), returned);
+                    }
+                    call_sources.insert(fc.name.clone(), owner_file);
+                }
+                continue;
+            }
+
+            if let Some(class) = constructed_class(fc) {
+                // Constructor: retorna una instancia de la clase (primer eslabón de `Product().price()`)
+                call_sources.insert(fc.name.clone(), class.module.clone());
+                call_contexts.insert(fc.name.clone(), vec![class]);
+                continue;
+            }
+
+            // Las llamadas encadenadas y las de objeto se resuelven en las pasadas siguientes
+            if fc.chain_source_fn.is_some() || fc.object_name.is_some() { continue; }
+
+            if let Some(module) = fc.import_name.as_deref() {
+                // Función importada directamente: module.func() o func() de `from X import func`
+                if let Some(src_file) = imports_hashmap.get(module)
+                    && store_snapshot.contains_key(Path::new(src_file))
+                {
+                    call_sources.insert(fc.name.clone(), src_file.clone());
+                    let returned = imported_return_classes(module, &fc.name);
+                    if !returned.is_empty() {
+                        call_contexts.insert(fc.name.clone(), returned);
+                    }
+                }
+            } else if let Some(local_fn) = analysis.function(&fc.name) {
+                // Función del mismo archivo
+                call_sources.insert(fc.name.clone(), path_string.to_string());
+                let returned = local_fn.return_type.as_deref().map(annotation_classes).unwrap_or_default();
+                if !returned.is_empty() {
+                    call_contexts.insert(fc.name.clone(), returned);
+                }
+            }
+        }
+
+        // ── Pasada iterativa: resolver cadenas encadenadas de cualquier profundidad
+        //
+        // Ejemplo: hola().chau().pepe()
+        //   iteración 1 → resuelve chau (chain_source_fn="hola", hola ya está en call_contexts)
+        //   iteración 2 → resuelve pepe (chain_source_fn="chau", chau ya está en call_contexts)
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for fc in function_calls {
+                let Some(source_fn) = fc.chain_source_fn.as_deref() else { continue };
+                if call_sources.contains_key(&fc.name) { continue; }
+
+                if let Some(source_types) = call_contexts.get(source_fn).cloned() {
+                    // El método fc.name vive en la(s) clase(s) que retorna el eslabón anterior
+                    call_sources.insert(fc.name.clone(), source_types[0].module.clone());
+
+                    // Intentar propagar el return_type para el siguiente eslabón
+                    let mut returned: Vec<SymbolId> = vec![];
+                    for class in source_types.iter().flat_map(|t| method_return_classes(&t.module, &t.name, &fc.name)) {
+                        if !returned.contains(&class) {
+                            returned.push(class);
+                        }
+                    }
+                    if !returned.is_empty() {
+                        call_contexts.insert(fc.name.clone(), returned);
+                    }
+                    changed = true;
+                }
+            }
+        }
+
+        // ── Loop principal: construir Connections usando los mapas pre-computados
+        for function_call in function_calls {
+            let name = function_call.name.as_str();
+            let FunctionCall { line, start_col, end_col, .. } = *function_call;
+
+            if let Some(class) = self_call_class(function_call) {
+                // Caso 0: self.method() / cls.method()  →  método de la clase contenedora o de una de sus bases
+                if let Some((owner_file, owner)) = find_method_owner(path_string, &class.name, name) {
+                    new_connections.push(Connections {
+                        file_use: path_string.to_string(), line, start_col, end_col,
+                        target: SymbolId::method(&owner_file, &owner, name), caller: caller.clone(), dangling: false, kind: EdgeKind::Calls,
+                    });
+                }
+            } else if let Some(class) = constructed_class(function_call) {
+                // Caso 5: instanciación  →  el __init__ (propio o heredado) y la clase. Va antes que los casos
+                // 1 y 4 porque un constructor tiene la misma forma que una llamada a función; el __init__ va
+                // primero para que ir a la definición desde el call site salte a él.
+                if let Some(owner) = hierarchy.find_method_owner(&class, "__init__") {
+                    new_connections.push(Connections {
+                        file_use: path_string.to_string(), line, start_col, end_col,
+                        target: SymbolId::method(&owner.module, &owner.name, "__init__"),
+                        caller: caller.clone(), dangling: false, kind: EdgeKind::Instantiates,
+                    });
+                }
+                new_connections.push(Connections {
+                    file_use: path_string.to_string(), line, start_col, end_col,
+                    target: class, caller: caller.clone(), dangling: false, kind: EdgeKind::Instantiates,
+                });
+            } else if let Some(import_module) = function_call.import_name.as_deref() {
+                // Caso 1: llamada directa a función importada (sólo si el módulo es parte del workspace).
+                // Si el módulo se borró, la llamada queda `dangling`; si todavía no se analizó, no genera edge.
+                if let Some(path) = imports_hashmap.get(import_module) {
+                    let dangling = !store_snapshot.contains_key(Path::new(path));
+                    if !dangling || deleted_modules.contains(Path::new(path)) {
+                        new_connections.push(Connections {
+                            file_use: path_string.to_string(), line, start_col, end_col,
+                            target: SymbolId::function(path, name), caller: caller.clone(), dangling, kind: EdgeKind::Calls,
+                        });
+                    }
+                }
+            } else if let Some(obj_name) = function_call.object_name.as_deref() {
+                // Caso 2: método sobre variable  →  obj.method()
+                // Prioridad: atributo de instancia (self.attr) → local_variables (anotada, variable de un
+                //            `for` o asignada desde función/constructor) → parameters (tipo anotado)
+                let attribute_classes = obj_name
+                    .strip_prefix("self.")
+                    .zip(enclosing_class)
+                    .map(|(attribute, class)| {
+                        hierarchy.attribute_types(&SymbolId::class(path_string, &class.name), attribute)
+                    })
+                    .unwrap_or_default();
+                let local = function.local_variable(obj_name);
+                // Anotación de lo que recorre un `for`: un parámetro o una variable local anotada
+                let iterable_annotation = |iterable: &str| {
+                    function
+                        .parameter(iterable)
+                        .and_then(|p| p.param_type.as_deref())
+                        .or_else(|| function.local_variable(iterable)?.annotation.as_deref())
+                };
+
+                // Una Connection por clase candidata (p. ej. cada alternativa de `Cart | Order`)
+                let receiver_classes: Vec<SymbolId> = if !attribute_classes.is_empty() {
+                    // self.repo.find_user()  →  tipo inferido en __init__ o en las anotaciones de la clase
+                    attribute_classes
+                } else if let Some(annotation) = local.and_then(|v| v.annotation.as_deref()) {
+                    // p: Product = ...
+                    annotation_classes(annotation)
+                } else if let Some(iterable) = local.and_then(|v| v.iterates_over.as_deref()) {
+                    // for p in products  (products: list[Product])  →  p es un Product
+                    iterable_annotation(iterable)
+                        .map(|a| hierarchy.element_classes(original_path, a))
+                        .unwrap_or_default()
+                } else if let Some(assigned_func) = local.and_then(|v| v.assigned_from.as_deref()) {
+                    let source_call = function_calls.iter().find(|c| c.name == assigned_func);
+                    if let Some(class) = source_call.and_then(&constructed_class) {
+                        // p = Product(...)  →  la variable es una instancia de la clase
+                        vec![class]
+                    } else if let Some(module) = source_call.and_then(|c| c.import_name.as_deref()) {
+                        imported_return_classes(module, assigned_func)
+                    } else {
+                        vec![]
+                    }
+                } else {
+                    // Fallback: obj_name podría ser un parámetro con tipo anotado
+                    // ej: def f(product: Optional[Product]) → product.price()
+                    function
+                        .parameter(obj_name)
+                        .and_then(|p| p.param_type.as_deref())
+                        .map(annotation_classes)
+                        .unwrap_or_default()
+                };
+
+                for target in receiver_classes.iter().filter_map(|class| resolve_method(&class.module, &class.name, name)) {
+                    new_connections.push(Connections {
+                        file_use: path_string.to_string(), line, start_col, end_col,
+                        target, caller: caller.clone(), dangling: false, kind: EdgeKind::Calls,
+                    });
+                }
+            } else if let Some(source_fn) = function_call.chain_source_fn.as_deref() {
+                // Caso 3: llamada encadenada  →  resuelto en la pasada iterativa
+                if let Some(src_file) = call_sources.get(source_fn) {
+                    // El método vive en la(s) clase(s) que retorna el eslabón anterior
+                    let targets: Vec<SymbolId> = match call_contexts.get(source_fn) {
+                        Some(source_types) => source_types
+                            .iter()
+                            .filter_map(|t| resolve_method(&t.module, &t.name, name))
+                            .collect(),
+                        None => vec![SymbolId::function(src_file, name)],
+                    };
+                    for target in targets {
+                        new_connections.push(Connections {
+                            file_use: path_string.to_string(), line, start_col, end_col,
+                            target, caller: caller.clone(), dangling: false, kind: EdgeKind::Calls,
+                        });
+                    }
+                }
+            } else if analysis.function(name).is_some() {
+                // Caso 4: llamada local directa (misma función en mismo archivo)
+                new_connections.push(Connections {
+                    file_use: path_string.to_string(), line, start_col, end_col,
+                    target: SymbolId::function(path_string, name), caller: caller.clone(), dangling: false, kind: EdgeKind::Calls,
+                });
+            }
+        }
+
+        new_connections
+    };
+
+    // Procesar clases
+    for class in &analysis.classes {
+        for method in &class.methods {
+            let new_connections = process_function_calls(
+                method,
+                Some(class),
+                &SymbolId::method(&path_string, &class.name, &method.name),
+                &path_string,
+                &imports_hashmap,
+            );
+
+            all_connections.extend(new_connections);
+        }
+    }
+
+    // Procesar funciones top-level
+    for func in &analysis.functions {
+        let new_connections = process_function_calls(
+            func,
+            None,
+            &SymbolId::function(&path_string, &func.name),
+            &path_string,
+            &imports_hashmap,
+        );
+
+        all_connections.extend(new_connections);
+    }
+
+    all_connections
+}
+
+/// Convierte una URI `file://` serializada (como llega en `FileRename`) en path local.
+fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    Url::parse(uri).ok()?.to_file_path().ok()
+}
+
+/// Filtro de operaciones de archivo (rename/move) para archivos `.py` y carpetas del workspace.
+fn python_file_operation_registration() -> FileOperationRegistrationOptions {
+    FileOperationRegistrationOptions {
+        filters: vec![
+            FileOperationFilter {
+                scheme: Some("file".to_string()),
+                pattern: FileOperationPattern {
+                    glob: "**/*.py".to_string(),
+                    matches: Some(FileOperationPatternKind::File),
+                    options: None,
+                },
+            },
+            FileOperationFilter {
+                scheme: Some("file".to_string()),
+                pattern: FileOperationPattern {
+                    glob: "**".to_string(),
+                    matches: Some(FileOperationPatternKind::Folder),
+                    options: None,
+                },
+            },
+        ],
+    }
+}
+
 impl Backend {
+    fn new(client: Client) -> Backend {
+        Backend {
+            state: Arc::new(BackendState {
+                client,
+                store: RwLock::new(HashMap::new()),
+                connections: RwLock::new(vec![]),
+                functions_in_file: RwLock::new(vec![]),
+                dependency_index: RwLock::new(DependencyIndex::default()),
+                content_hashes: RwLock::new(HashMap::new()),
+                deleted_modules: RwLock::new(HashSet::new()),
+                open_documents: RwLock::new(HashMap::new()),
+                analysis_queue: Mutex::new(AnalysisQueue::default()),
+                analysis_signal: Notify::new(),
+                batch_lock: Mutex::new(()),
+                analysis_workers: RwLock::new(analysis_workers_from_options(None)),
+                analysis_epoch: AtomicU64::new(0),
+                graph_state: Mutex::new(GraphState::default()),
+                quarantine: RwLock::new(Quarantine::default()),
+                workspace_root: RwLock::new(std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."))),
+                ignored_folders: RwLock::new(vec![]),
+            }),
+        }
+    }
+
     /// Recarga la lista de carpetas ignoradas leyendo `.lspignore` desde el workspace actual.
     async fn reload_ignore_list(&self) {
         let root = { self.workspace_root.read().await.clone() };
@@ -324,9 +887,20 @@ impl Backend {
         *guard = list;
     }
 
+    /// Lanza el escaneo inicial en una tarea propia, atado al epoch de cancelación vigente: así el
+    /// handler que lo arranca no queda bloqueado y `cancel_analysis` lo puede abortar.
+    fn start_workspace_scan(&self) -> tokio::task::JoinHandle<()> {
+        let epoch = self.analysis_epoch.load(Ordering::SeqCst);
+        let backend = self.clone();
+        tokio::spawn(async move { backend.analyze_workspace(epoch).await })
+    }
+
     /// Escanea todos los archivos `.py` del workspace, los analiza con Tree-sitter
     /// (usando caché cuando el contenido no cambió) y envía los resultados al frontend.
-    async fn analyze_workspace(&self) {
+    /// Si se cancela después de `epoch`, deja de arrancar archivos y resuelve sólo lo ya analizado.
+    async fn analyze_workspace(&self, epoch: u64) {
+        // Los lotes del worker esperan a que termine el escaneo
+        let _running = self.batch_lock.lock().await;
         let root = { self.workspace_root.read().await.clone() };
         let ignored = { self.ignored_folders.read().await.clone() };
 
@@ -343,7 +917,7 @@ impl Backend {
             };
             while let Ok(Some(entry)) = read_dir.next_entry().await {
                 let path = entry.path();
-                if is_ignored(&path, &ignored) {
+                if is_ignored(&path, &ignored) || is_internal_path(&path, &root) {
                     continue;
                 }
                 let Ok(ft) = entry.file_type().await else {
@@ -366,45 +940,206 @@ impl Backend {
             )
             .await;
 
-        for path in &py_files {
-            let file_bytes = match fs::read(path).await {
-                Ok(b) => b,
-                Err(_) => continue,
-            };
-            let content_hash = hash_content(&file_bytes);
+        // Fase 1: parsear (o cargar de caché) todos los archivos, en paralelo, sin resolver nada todavía.
+        // Resolver acá haría que cada archivo sólo vea a los analizados antes que él.
+        let workers = { *self.analysis_workers.read().await };
+        let total = py_files.len();
+        let progress = WorkProgress::begin(&self.client, "Analyzing workspace", total).await;
+        // `None` si el archivo no se procesó, `Some(None)` si quedó en cuarentena
+        let outcomes: Vec<Option<(PathBuf, String, FileAnalysis)>> = futures::stream::iter(py_files.iter().cloned())
+            .map(|path| {
+                let root = root.clone();
+                async move {
+                    // Cancelado: no arrancamos análisis nuevos, los ya terminados se conservan
+                    if self.is_cancelled(epoch) {
+                        return None;
+                    }
+                    let file_bytes = fs::read(&path).await.ok()?;
+                    let content_hash = hash_content(&file_bytes);
 
-            // Intentar warm-up desde caché
-            if let Some(cached_value) = self.try_load_from_cache(path, &content_hash).await {
-                self.upsert_store_value(path, &cached_value).await;
-                self.save_function_reference(path, &cached_value).await;
-                self.save_functions(path, &cached_value).await;
-                continue;
-            }
+                    // Intentar warm-up desde caché
+                    if let Some(cached) = self.try_load_from_cache(&path, &content_hash).await {
+                        return Some(Some((path, content_hash, cached)));
+                    }
+
+                    // Cache miss: analizar desde cero
+                    let path_clone = path.clone();
+                    let result =
+                        tokio::task::spawn_blocking(move || run_analysis(&path_clone, &[root])).await;
+                    let json_str = match result.unwrap_or(Err("spawn_blocking failed".to_string())) {
+                        Ok(json_str) => json_str,
+                        Err(err) => {
+                            self.quarantine_file(&path, format!("analyzer failed: {err}")).await;
+                            return Some(None);
+                        }
+                    };
+                    let analysis = match FileAnalysis::parse(&json_str) {
+                        Ok(analysis) => analysis,
+                        Err(err) => {
+                            self.quarantine_file(&path, err.to_string()).await;
+                            return Some(None);
+                        }
+                    };
+                    self.log_analysis_warnings(&path, &analysis).await;
+                    let _ = self.persist_analysis_json(&path, &analysis, &content_hash).await;
+                    Some(Some((path, content_hash, analysis)))
+                }
+            })
+            .buffer_unordered(workers)
+            .enumerate()
+            .filter_map(|(done, parsed)| {
+                let progress = &progress;
+                async move {
+                    progress.report(done + 1).await;
+                    parsed
+                }
+            })
+            .collect()
+            .await;
+        let quarantined = outcomes.iter().filter(|outcome| outcome.is_none()).count();
+        let mut parsed: Vec<(PathBuf, String, FileAnalysis)> = outcomes.into_iter().flatten().collect();
+        progress.end(self.progress_summary(epoch, parsed.len(), quarantined, total)).await;
+
+        // Merge al store en orden de path, una vez terminados todos los workers. Si se canceló,
+        // el store queda con los archivos completos y la fase 2 los resuelve entre sí; el resto
+        // se retoma con `lsp-server/rescanWorkspace`.
+        parsed.sort_by(|a, b| a.0.cmp(&b.0));
+        for (path, content_hash, analysis) in &parsed {
+            self.remember_content_hash(path, content_hash).await;
+            self.upsert_store_value(path, analysis).await;
+            self.release_file(path).await;
+        }
 
-            // Cache miss: analizar desde cero
-            let path_clone = path.clone();
-            let root_clone = root.clone();
-            let result =
-                tokio::task::spawn_blocking(move || run_analysis(&path_clone, &[root_clone])).await;
-            if let Ok(Ok(json_str)) = result {
-                let value: Value = serde_json::from_str(&json_str)
-                    .unwrap_or_else(|_| serde_json::json!({ "raw": json_str }));
-                self.upsert_store_value(path, &value).await;
-                self.save_function_reference(path, &value).await;
-                self.save_functions(path, &value).await;
-                let _ = self.persist_analysis_json(path, &value, &content_hash).await;
+        // Fase 2: con el store completo, indexar funciones y resolver connections de todos los archivos
+        {
+            let store_snapshot = { self.store.read().await.clone() };
+            let mut paths: Vec<&PathBuf> = store_snapshot.keys().collect();
+            paths.sort();
+            for path in paths {
+                self.save_functions(path, &store_snapshot[path]).await;
             }
         }
+        self.resolve_all_connections().await;
+
+        // Los archivos que no se pudieron analizar se reportan sobre el propio archivo
+        if !self.quarantine.read().await.is_empty() {
+            self.publish_workspace_diagnostics().await;
+        }
+
+        // La carga inicial va completa; a partir de acá el cliente recibe deltas sobre esta revisión
+        if !py_files.is_empty()
+            && let Ok(payload) = self.graph_snapshot().await
+        {
+            self.client.send_notification::<ProcessedJson>(payload).await;
+        }
+    }
+
+    /// Cancela los análisis en curso (escaneo inicial y lotes): dejan de arrancar archivos nuevos
+    /// y resuelven sólo lo ya analizado, así el store queda consistente.
+    ///
+    /// Se dispara desde el request `lsp-server/cancelAnalysis` y desde la notificación
+    /// `window/workDoneProgress/cancel` (el botón de cancelar del progreso). `$/cancelRequest` no
+    /// aplica: tower-lsp lo resuelve internamente y sólo aborta requests en curso, y el escaneo y los
+    /// lotes corren en tareas propias, fuera de cualquier request.
+    fn cancel_analysis(&self) {
+        self.analysis_epoch.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Retorna `true` si se pidió cancelar después de que arrancó el análisis de `epoch`.
+    fn is_cancelled(&self, epoch: u64) -> bool {
+        self.analysis_epoch.load(Ordering::SeqCst) != epoch
+    }
+
+    /// Mensaje final del progreso de un análisis, con los archivos que efectivamente se analizaron
+    /// y los que quedaron en cuarentena.
+    fn progress_summary(&self, epoch: u64, analyzed: usize, quarantined: usize, total: usize) -> String {
+        let mut summary = if self.is_cancelled(epoch) {
+            format!("Analysis cancelled after {}", progress::files_message(analyzed + quarantined, total))
+        } else {
+            format!("Analyzed {analyzed} files")
+        };
+        if quarantined > 0 {
+            summary.push_str(&format!(" ({quarantined} quarantined)"));
+        }
+        summary
+    }
+
+    /// Notificación `window/workDoneProgress/cancel`: el usuario canceló el progreso desde el editor.
+    async fn work_done_progress_cancel(&self, _params: WorkDoneProgressCancelParams) {
+        self.cancel_analysis();
+    }
+
+    /// Request custom `lsp-server/rescanWorkspace`: vuelve a escanear el workspace (por ejemplo,
+    /// después de cancelar el escaneo inicial) y despierta al worker para los lotes que quedaron
+    /// pendientes. Los archivos sin cambios se cargan desde la caché.
+    async fn rescan_workspace(&self) -> Result<()> {
+        self.start_workspace_scan();
+        self.analysis_signal.notify_one();
+        Ok(())
+    }
+
+    /// Request custom `lsp-server/cancelAnalysis`: cancela los análisis en curso.
+    async fn cancel_analysis_request(&self) -> Result<()> {
+        self.cancel_analysis();
+        self.client
+            .log_message(MessageType::INFO, "Analysis cancelled by client")
+            .await;
+        Ok(())
+    }
+
+    /// Grafo actual: archivos del store, llamadas resueltas y herencia entre clases, con paths relativos al workspace.
+    async fn current_graph(&self) -> (Vec<LspFileMessage>, BTreeSet<GraphEdge>) {
+        let root = { self.workspace_root.read().await.clone() };
+        let files = format_for_lsp_message(self.store.read().await, root.clone());
+        let mut edges: BTreeSet<GraphEdge> = self
+            .connections
+            .read()
+            .await
+            .iter()
+            .map(|c| GraphEdge::from_connection(c, &root))
+            .collect();
+        {
+            let store = self.store.read().await;
+            edges.extend(
+                ClassHierarchy::new(&store)
+                    .inheritance()
+                    .iter()
+                    .map(|i| GraphEdge::from_inheritance(i, &root)),
+            );
+        }
+        (files, edges)
+    }
 
-        if !py_files.is_empty() {
-            let map = self.store.read().await;
-            let message = format_for_lsp_message(map, root.clone());
+    /// Envía al cliente sólo lo que cambió desde el último grafo enviado (`lsp-server/graphDelta`).
+    async fn publish_graph_delta(&self) {
+        // El grafo se arma con el lock tomado, así un grafo más viejo nunca pisa una revisión más
+        // nueva; el lock se mantiene durante el envío para que los deltas salgan en orden de revisión
+        let mut state = self.graph_state.lock().await;
+        let (files, edges) = self.current_graph().await;
+        if let Some(delta) = state.advance(files_by_name(&files), edges) {
             self.client
-                .send_notification::<ProcessedJson>(ProcessedJsonPayload { files: message })
+                .send_notification::<GraphDeltaNotification>(delta)
                 .await;
         }
     }
 
+    /// Request custom `lsp-server/graphSnapshot`: el grafo completo y su revisión. El cliente lo pide
+    /// al arrancar o cuando detecta un salto de revisión en los deltas.
+    async fn graph_snapshot(&self) -> Result<ProcessedJsonPayload> {
+        // Igual que en `publish_graph_delta`: el grafo se arma con el lock tomado
+        let (files, edges, revision) = {
+            let mut state = self.graph_state.lock().await;
+            let (files, edges) = self.current_graph().await;
+            let revision = state.replace(files_by_name(&files), edges.clone());
+            (files, edges, revision)
+        };
+        Ok(ProcessedJsonPayload {
+            revision,
+            files,
+            edges: edges.into_iter().collect(),
+        })
+    }
+
     /// Registra watchers de sistema de archivos para detectar cambios en cualquier archivo del workspace.
     async fn register_fs_watchers(&self) {
         let watchers = vec![FileSystemWatcher {
@@ -422,493 +1157,930 @@ impl Backend {
         let _ = self.client.register_capability(vec![reg]).await;
     }
 
-    /// Procesa un evento de cambio de archivo individual (creado, modificado o eliminado).
-    /// Re-analiza el archivo si es `.py`, actualiza el store y notifica al frontend.
-    async fn process_path_change(&self, path: &std::path::Path, typ: FileChangeType) {
-        // Si se modificó .lspignore, recargar la lista y salir
-        let root = { self.workspace_root.read().await.clone() };
-        if path == root.join(".lspignore") {
-            self.reload_ignore_list().await;
-            return;
-        }
-
-        if path.extension().and_then(|e| e.to_str()) != Some("py") && path.extension().and_then(|e| e.to_str()) != Some("js") {
-          return;
-        }
-
-        // Saltear archivos en carpetas ignoradas
+    /// Encola eventos de archivos en la cola de re-análisis y despierta al worker. No espera
+    /// el análisis: el handler que llama queda libre enseguida.
+    async fn enqueue_changes(&self, changes: Vec<(PathBuf, FileChangeType)>) {
         {
-            let ignored = self.ignored_folders.read().await;
-            if is_ignored(path, &ignored) {
-                return;
+            let mut queue = self.analysis_queue.lock().await;
+            for (path, typ) in changes {
+                // Nos quedamos con el último evento de cada path
+                queue.pending.insert(path, typ);
             }
         }
+        self.analysis_signal.notify_one();
+    }
 
-        match typ {
-            FileChangeType::CREATED | FileChangeType::CHANGED => {
-                let root = { self.workspace_root.read().await.clone() };
-                let path_clone = path.to_path_buf();
-                let root_clone = root.clone();
-                let result =
-                    tokio::task::spawn_blocking(move || run_analysis(&path_clone, &[root_clone]))
-                        .await;
-                if let Ok(Ok(json_str)) = result {
-                    let value: serde_json::Value = serde_json::from_str(&json_str)
-                        .unwrap_or_else(|_| serde_json::json!({ "raw": json_str }));
-                    self.upsert_store_value(path, &value).await;
-                    self.save_function_reference(&path, &value).await;
-                    self.save_functions(&path, &value).await;
-
-                    // Notifica al cliente con el agregado de este archivo
-                    let map = self.store.read().await;
-                    let message = format_for_lsp_message(map, root.clone());
-                    self.client
-                        .send_notification::<ProcessedJson>(ProcessedJsonPayload { files: message })
-                        .await;
-
-                    // Persiste a disco (ignora error no fatal)
-                    let file_bytes = fs::read(path).await.unwrap_or_default();
-                    let content_hash = hash_content(&file_bytes);
-                    let _ = self.persist_analysis_json(path, &value, &content_hash).await;
+    /// Worker de re-análisis (una única tarea de fondo). Espera eventos encolados, hace el debounce
+    /// (cada evento nuevo lo reinicia) y procesa el lote completo, sin paths duplicados.
+    async fn run_analysis_worker(self) {
+        loop {
+            self.analysis_signal.notified().await;
+            loop {
+                tokio::select! {
+                    _ = self.analysis_signal.notified() => continue,
+                    _ = tokio::time::sleep(ANALYSIS_DEBOUNCE) => break,
                 }
             }
-            FileChangeType::DELETED => {
-                // Opcional: borrar del store y del cache en disco
-                {
-                    let mut guard = self.store.write().await;
-                    guard.remove(path);
-                }
-                let root = { self.workspace_root.read().await.clone() };
-                let base = cache_root_for_workspace(&root);
-                let file_id = hash_path(path);
-                let target = base.join(format!("{file_id}.json"));
-                let _ = tokio::fs::remove_file(target).await;
+            let (batch, saved, renames) = {
+                let mut queue = self.analysis_queue.lock().await;
+                let batch = std::mem::take(&mut queue.pending);
+                // Un guardado marcado después de tomar el lote queda para el próximo
+                let saved: HashSet<PathBuf> = batch.keys().filter(|p| queue.saved.remove(*p)).cloned().collect();
+                let renames = std::mem::take(&mut queue.renames);
+                (batch, saved, renames)
+            };
+            if !batch.is_empty() || !renames.is_empty() {
+                self.process_batch(batch, saved, renames).await;
             }
-            _ => {}
         }
     }
 
-    /// Guarda/actualiza el JSON analizado del archivo en el store en memoria.
-    async fn upsert_store_value(&self, original_path: &Path, value: &Value) {
-        let mut guard = self.store.write().await;
-        guard.insert(original_path.to_path_buf(), value.clone());
-    }
-
-    async fn save_function_reference(&self, original_path: &Path, value: &Value) {
-        let binding = value.clone();
-        let path_string = original_path.to_str().unwrap().to_string();
-
-        // Snapshot del store antes de cualquier lock de connections
-        let store_snapshot = {
-            self.store.read().await.clone()
+    /// Procesa un lote coalescido de eventos bajo `batch_lock` (ver `apply_batch`). Las preguntas
+    /// de reescritura de imports por los moves detectados se lanzan después, sin el lock tomado:
+    /// el worker no queda esperando a que el usuario conteste.
+    async fn process_batch(
+        &self,
+        batch: HashMap<PathBuf, FileChangeType>,
+        saved: HashSet<PathBuf>,
+        renames: Vec<(PathBuf, PathBuf)>,
+    ) {
+        let moved = {
+            // Un lote a la vez: evita carreras sobre store/connections entre lotes
+            let _running = self.batch_lock.lock().await;
+            self.apply_batch(batch, saved, renames).await
         };
 
-        {
-            let mut connections = self.connections.write().await;
-            connections.retain(|c| c.file_use != path_string);
+        for (old, new) in moved {
+            let backend = self.clone();
+            tokio::spawn(async move { backend.offer_import_rewrite(&old, &new).await });
         }
+    }
 
-        let mut imports_hashmap: HashMap<String, String> = HashMap::new();
-        let imports = binding
-            .get("imports")
-            .and_then(|v| v.as_array())
-            .expect("imports no es un array");
-
-        for import in imports {
-            let name = import.get("name").and_then(|v| v.as_str()).unwrap_or("");
-            let path = import.get("path").and_then(|v| v.as_str()).unwrap_or("");
-            imports_hashmap.insert(name.to_string(), path.to_string());
-        }
-
-        // Helper closure: dado un import_module y un function name, 
-        // resuelve el return_type buscando en el store
-        let resolve_return_type = |import_module: &str, func_name: &str| -> Option<String> {
-            let file_path = imports_hashmap.get(import_module)?;
-            let file_value = store_snapshot.get(&PathBuf::from(file_path))?;
-            
-            // buscar en funciones top-level
-            let functions = file_value.get("functions")?.as_array()?;
-            for func in functions {
-                if func.get("name")?.as_str()? == func_name {
-                    return func.get("return_type")
-                        .filter(|v| !v.is_null())
-                        .and_then(|v| v.as_str())
-                        .map(|s| s.to_string());
-                }
+    /// Aplica un lote: renames del editor, moves, borrados y re-análisis con concurrencia acotada.
+    /// Resuelve el lote y sus dependientes una sola vez y emite una única actualización al cliente.
+    /// Para los archivos de `saved` (guardados desde el editor) avisa además de cambios de firma.
+    /// Retorna los moves detectados por el watcher, para ofrecer reescribir sus imports.
+    async fn apply_batch(
+        &self,
+        batch: HashMap<PathBuf, FileChangeType>,
+        saved: HashSet<PathBuf>,
+        renames: Vec<(PathBuf, PathBuf)>,
+    ) -> Vec<(PathBuf, PathBuf)> {
+        // Los renames del editor ya reescribieron sus imports (`willRenameFiles`): sólo se migran.
+        // Los eventos que el watcher manda después por esos mismos archivos se esperan y descartan
+        let mut migrated_files = 0;
+        for (old, new) in &renames {
+            let files = self.migrate_file(old, new).await;
+            migrated_files += files.len();
+            let mut queue = self.analysis_queue.lock().await;
+            for (from, to) in files {
+                queue.expected_events.insert(from, FileChangeType::DELETED);
+                queue.expected_events.insert(to, FileChangeType::CREATED);
             }
-            None
-        };
+        }
 
-        // Helper closure: dado un type_name, encuentra el path del archivo que define esa clase
-        let find_class_file = |type_name: &str| -> Option<String> {
-            for (path, file_value) in &store_snapshot {
-                let classes = file_value.get("classes")?.as_array()?;
-                for class in classes {
-                    if class.get("name")?.as_str()? == type_name {
-                        return Some(path.to_str()?.to_string());
-                    }
-                }
+        let root = { self.workspace_root.read().await.clone() };
+        let mut changes: Vec<(PathBuf, FileChangeType)> = vec![];
+        for (path, typ) in batch {
+            // Si se modificó .lspignore, recargar la lista
+            if path == root.join(".lspignore") {
+                self.reload_ignore_list().await;
+                continue;
             }
-            None
-        };
-
-        // Helper closure: dado el archivo que define una clase, su nombre y el nombre de un método,
-        // devuelve el return_type de ese método (para resolver cadenas de N niveles).
-        let find_method_return_type = |class_file: &str, class_name: &str, method_name: &str| -> Option<String> {
-            let file_value = store_snapshot.get(&PathBuf::from(class_file))?;
-            let classes = file_value.get("classes")?.as_array()?;
-            for class in classes {
-                if class.get("name")?.as_str()? == class_name {
-                    let methods = class.get("methods")?.as_array()?;
-                    for method in methods {
-                        if method.get("name")?.as_str()? == method_name {
-                            return method.get("return_type")
-                                .filter(|v| !v.is_null())
-                                .and_then(|v| v.as_str())
-                                .map(|s| s.to_string());
-                        }
-                    }
+            changes.push((path, typ));
+        }
+        {
+            let mut queue = self.analysis_queue.lock().await;
+            changes.retain(|(path, typ)| {
+                if queue.expected_events.get(path) == Some(typ) {
+                    queue.expected_events.remove(path);
+                    return false;
                 }
+                true
+            });
+        }
+        {
+            let ignored = self.ignored_folders.read().await;
+            changes.retain(|(path, _)| {
+                is_analyzable(path) && !is_ignored(path, &ignored) && !is_internal_path(path, &root)
+            });
+        }
+        if changes.is_empty() {
+            if migrated_files > 0 {
+                self.publish_workspace_diagnostics().await;
+                self.publish_graph_delta().await;
             }
+            return vec![];
+        }
+        changes.sort_by(|a, b| a.0.cmp(&b.0));
+
+        // Análisis y connections previos de los archivos guardados, para comparar firmas al final
+        let before_save = if changes.iter().any(|(path, _)| saved.contains(path)) {
+            let store = self.store.read().await;
+            let previous: HashMap<PathBuf, FileAnalysis> = saved
+                .iter()
+                .filter_map(|path| Some((path.clone(), store.get(path)?.clone())))
+                .collect();
+            Some((previous, self.connections.read().await.clone()))
+        } else {
             None
         };
 
-        let process_function_calls = |
-            function_calls: &Vec<Value>,
-            local_variables: &Vec<Value>,
-            parameters: &Vec<Value>,
-            path_string: &str,
-            imports_hashmap: &HashMap<String, String>,
-        | -> Vec<Connections> {
-            let mut new_connections = vec![];
-
-            // ── Pre-pass: construir dos mapas para habilitar resolución de cadenas N-profundas
-            //
-            // call_sources: call_name → archivo donde ese método/función está definido
-            //               (se usa como file_src en la Connection)
-            // call_contexts: call_name → (tipo_retornado, archivo_que_define_ese_tipo)
-            //               (se usa para resolver el SIGUIENTE eslabón de la cadena)
-            let mut call_sources: HashMap<String, String>          = HashMap::new();
-            let mut call_contexts: HashMap<String, (String, String)> = HashMap::new();
-
-            for fc in function_calls.iter() {
-                let fc_name   = fc.get("name").and_then(|v| v.as_str()).unwrap_or("");
-                let fc_import = fc.get("import_name").and_then(|v| v.as_str());
-                let fc_chain  = fc.get("chain_source_fn").and_then(|v| v.as_str());
-                let fc_object = fc.get("object_name").and_then(|v| v.as_str());
-
-                // Las llamadas encadenadas y las de objeto se resuelven en las pasadas siguientes
-                if fc_chain.is_some() || fc_object.is_some() { continue; }
-
-                if let Some(module) = fc_import {
-                    // Función importada directamente: module.func() o func() de `from X import func`
-                    if let Some(src_file) = imports_hashmap.get(module) {
-                        call_sources.insert(fc_name.to_string(), src_file.clone());
-                        if let Some(rt) = resolve_return_type(module, fc_name) {
-                            if let Some(rt_file) = find_class_file(&rt) {
-                                call_contexts.insert(fc_name.to_string(), (rt, rt_file));
-                            }
-                        }
-                    }
-                } else {
-                    // Función del mismo archivo
-                    let local_fn = binding.get("functions").and_then(|v| v.as_array())
-                        .and_then(|fns| fns.iter().find(|f| {
-                            f.get("name").and_then(|n| n.as_str()) == Some(fc_name)
-                        }));
-                    if local_fn.is_some() {
-                        call_sources.insert(fc_name.to_string(), path_string.to_string());
-                        if let Some(rt) = local_fn
-                            .and_then(|f| f.get("return_type"))
-                            .filter(|v| !v.is_null())
-                            .and_then(|v| v.as_str())
-                        {
-                            if let Some(rt_file) = find_class_file(rt) {
-                                call_contexts.insert(fc_name.to_string(), (rt.to_string(), rt_file));
-                            }
-                        }
-                    }
-                }
-            }
+        // Un DELETED + CREATED con el mismo contenido es un move: migramos en vez de re-analizar
+        let (moved, changes) = self.pair_moves(changes).await;
+        for (old, new) in &moved {
+            migrated_files += self.migrate_file(old, new).await.len();
+        }
 
-            // ── Pasada iterativa: resolver cadenas encadenadas de cualquier profundidad
-            //
-            // Ejemplo: hola().chau().pepe()
-            //   iteración 1 → resuelve chau (chain_source_fn="hola", hola ya está en call_contexts)
-            //   iteración 2 → resuelve pepe (chain_source_fn="chau", chau ya está en call_contexts)
-            let mut changed = true;
-            while changed {
-                changed = false;
-                for fc in function_calls.iter() {
-                    let fc_name  = fc.get("name").and_then(|v| v.as_str()).unwrap_or("");
-                    let fc_chain = fc.get("chain_source_fn").and_then(|v| v.as_str());
-
-                    let Some(source_fn) = fc_chain else { continue };
-                    if call_sources.contains_key(fc_name) { continue; }
-
-                    if let Some((source_type, source_file)) = call_contexts.get(source_fn).cloned() {
-                        // El método fc_name vive en la clase source_type, definida en source_file
-                        call_sources.insert(fc_name.to_string(), source_file.clone());
-
-                        // Intentar propagar el return_type para el siguiente eslabón
-                        if let Some(rt) = find_method_return_type(&source_file, &source_type, fc_name) {
-                            if let Some(rt_file) = find_class_file(&rt) {
-                                call_contexts.insert(fc_name.to_string(), (rt, rt_file));
-                            }
-                        }
-                        changed = true;
-                    }
-                }
+        let mut deleted: Vec<PathBuf> = vec![];
+        let mut to_analyze: Vec<PathBuf> = vec![];
+        for (path, typ) in changes {
+            match typ {
+                FileChangeType::CREATED | FileChangeType::CHANGED => to_analyze.push(path),
+                FileChangeType::DELETED => deleted.push(path),
+                _ => {}
             }
+        }
 
-            // ── Loop principal: construir Connections usando los mapas pre-computados
-            for function_call in function_calls {
-                let name        = function_call.get("name").and_then(|v| v.as_str()).unwrap_or("<sin nombre>");
-                let import_name = function_call.get("import_name").and_then(|v| v.as_str());
-                let object_name = function_call.get("object_name").and_then(|v| v.as_str());
-                let chain_source_fn = function_call.get("chain_source_fn").and_then(|v| v.as_str());
-                let line      = function_call.get("line").and_then(|v| v.as_i64()).unwrap_or(0);
-                let start_col = function_call.get("start_col").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
-                let end_col   = function_call.get("end_col").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
-
-                if let Some(import_module) = import_name {
-                    // Caso 1: llamada directa a función importada
-                    if let Some(path) = imports_hashmap.get(import_module) {
-                        new_connections.push(Connections {
-                            file_src: path.clone(), file_use: path_string.to_string(),
-                            line, start_col, end_col, function: name.to_string(),
-                        });
+        for path in &deleted {
+            self.forget_file(path).await;
+        }
+
+        let workers = { *self.analysis_workers.read().await };
+        let epoch = self.analysis_epoch.load(Ordering::SeqCst);
+        let total = to_analyze.len();
+        // Un único archivo (típicamente un buffer mientras se escribe) no amerita barra de progreso
+        let progress = if total > 1 {
+            Some(WorkProgress::begin(&self.client, "Re-analyzing changed files", total).await)
+        } else {
+            None
+        };
+        let results: Vec<(PathBuf, std::result::Result<String, String>, bool)> =
+            futures::stream::iter(to_analyze.clone())
+                .map(|path| async move {
+                    // Cancelado: los archivos que faltan conservan su análisis anterior
+                    if self.is_cancelled(epoch) {
+                        return None;
                     }
-                } else if let Some(obj_name) = object_name {
-                    // Caso 2: método sobre variable  →  obj.method()
-                    // Prioridad: local_variables (asignada desde función) → parameters (tipo anotado)
-                    let assigned_from = local_variables.iter()
-                        .find(|v| v.get("name").and_then(|n| n.as_str()) == Some(obj_name))
-                        .and_then(|v| v.get("assigned_from"))
-                        .and_then(|v| v.as_str());
-
-                    if let Some(assigned_func) = assigned_from {
-                        let source_import = function_calls.iter()
-                            .find(|c| c.get("name").and_then(|n| n.as_str()) == Some(assigned_func))
-                            .and_then(|c| c.get("import_name"))
-                            .and_then(|v| v.as_str());
-
-                        if let Some(module) = source_import {
-                            if let Some(return_type) = resolve_return_type(module, assigned_func) {
-                                if let Some(class_file) = find_class_file(&return_type) {
-                                    new_connections.push(Connections {
-                                        file_src: class_file, file_use: path_string.to_string(),
-                                        line, start_col, end_col, function: name.to_string(),
-                                    });
-                                }
-                            }
-                        }
-                    } else {
-                        // Fallback: obj_name podría ser un parámetro con tipo anotado
-                        // ej: def f(product: Product) → product.price()
-                        let param_type = parameters.iter()
-                            .find(|p| p.get("name").and_then(|n| n.as_str()) == Some(obj_name))
-                            .and_then(|p| p.get("param_type"))
-                            .and_then(|v| v.as_str());
-
-                        if let Some(type_annotation) = param_type {
-                            // Extraer el tipo base para anotaciones comunes:
-                            //   "Product"           → "Product"
-                            //   "Optional[Product]" → "Product"
-                            //   "Product | None"    → "Product"
-                            //   '"Product"'         → "Product"  (forward ref)
-                            let base_type = {
-                                let t = type_annotation.trim().trim_matches('"').trim_matches('\'');
-                                if let Some(inner) = t.strip_prefix("Optional[").and_then(|s| s.strip_suffix(']')) {
-                                    inner.trim()
-                                } else if let Some(base) = t.split('|').next() {
-                                    base.trim()
-                                } else {
-                                    t
-                                }
-                            };
-                            if let Some(class_file) = find_class_file(base_type) {
-                                new_connections.push(Connections {
-                                    file_src: class_file, file_use: path_string.to_string(),
-                                    line, start_col, end_col, function: name.to_string(),
-                                });
-                            }
+                    let (result, from_buffer) = self.analyze_file(&path).await;
+                    Some((path, result, from_buffer))
+                })
+                .buffer_unordered(workers)
+                .enumerate()
+                .filter_map(|(done, result)| {
+                    let progress = progress.as_ref();
+                    async move {
+                        if let Some(progress) = progress {
+                            progress.report(done + 1).await;
                         }
+                        result
                     }
-                } else if let Some(source_fn) = chain_source_fn {
-                    // Caso 3: llamada encadenada  →  resuelto en la pasada iterativa
-                    if let Some(src_file) = call_sources.get(source_fn) {
-                        new_connections.push(Connections {
-                            file_src: src_file.clone(), file_use: path_string.to_string(),
-                            line, start_col, end_col, function: name.to_string(),
-                        });
-                    }
-                } else {
-                    // Caso 4: llamada local directa (misma función en mismo archivo)
-                    let defined_in_same_file = binding.get("functions").and_then(|v| v.as_array())
-                        .map(|funcs| funcs.iter().any(|f| {
-                            f.get("name").and_then(|n| n.as_str()) == Some(name)
-                        }))
-                        .unwrap_or(false);
-
-                    if defined_in_same_file {
-                        new_connections.push(Connections {
-                            file_src: path_string.to_string(), file_use: path_string.to_string(),
-                            line, start_col, end_col, function: name.to_string(),
-                        });
-                    }
+                })
+                .collect()
+                .await;
+
+        // Los archivos que la cancelación dejó sin analizar vuelven a la cola: se procesan con el
+        // próximo lote (o con `lsp-server/rescanWorkspace`) en vez de perderse
+        let skipped: Vec<PathBuf> = to_analyze
+            .into_iter()
+            .filter(|path| !results.iter().any(|(done, _, _)| done == path))
+            .collect();
+        if !skipped.is_empty() {
+            let mut queue = self.analysis_queue.lock().await;
+            for path in skipped {
+                // Un evento más nuevo del mismo path ya está en la cola y tiene prioridad
+                queue.pending.entry(path).or_insert(FileChangeType::CHANGED);
+            }
+        }
+
+        // Cargamos todo el lote al store antes de resolver, para que sus archivos se vean entre sí
+        // Un archivo que falla queda en cuarentena con su análisis anterior; el resto del lote sigue
+        let mut analyzed: Vec<PathBuf> = vec![];
+        let mut rejected = 0;
+        for (path, result, from_buffer) in results {
+            let parsed = match result {
+                Ok(json_str) => FileAnalysis::parse(&json_str).map_err(|err| err.to_string()),
+                Err(err) => Err(format!("analyzer failed: {err}")),
+            };
+            let mut analysis = match parsed {
+                Ok(analysis) => analysis,
+                Err(reason) => {
+                    self.quarantine_file(&path, reason).await;
+                    rejected += 1;
+                    continue;
                 }
+            };
+            self.log_analysis_warnings(&path, &analysis).await;
+            if from_buffer {
+                // El analizador resolvió los imports desde el archivo sombra, no desde `path`
+                let store = self.store.read().await;
+                overlay_imports(&mut analysis, &buffers_root_for_workspace(&root), &root, store.get(&path));
             }
+            self.release_file(&path).await;
+            self.upsert_store_value(&path, &analysis).await;
+            self.save_functions(&path, &analysis).await;
 
-            new_connections
+            // El análisis de un buffer sin guardar no corresponde al contenido en disco: no se cachea
+            if !from_buffer {
+                let file_bytes = fs::read(&path).await.unwrap_or_default();
+                let content_hash = hash_content(&file_bytes);
+                self.remember_content_hash(&path, &content_hash).await;
+                let _ = self.persist_analysis_json(&path, &analysis, &content_hash).await;
+            }
+            analyzed.push(path);
+        }
+        analyzed.sort();
+        if let Some(progress) = progress {
+            progress.end(self.progress_summary(epoch, analyzed.len(), rejected, total)).await;
+        }
+
+        // El lote y sus dependientes se resuelven juntos contra un único snapshot. Los dependientes
+        // de los borrados también: sus llamadas hacia ellos pasan a `dangling`
+        let changed: Vec<PathBuf> = analyzed.iter().chain(&deleted).cloned().collect();
+        let mut to_resolve = analyzed.clone();
+        to_resolve.extend(self.dependents_of(&changed).await);
+        self.resolve_paths(&to_resolve).await;
+
+        if analyzed.is_empty() && deleted.is_empty() && rejected == 0 && migrated_files == 0 {
+            return moved;
+        }
+
+        // Una sola actualización por lote (si un archivo vuelve a existir, sus callers dejan de estar `dangling`)
+        self.publish_workspace_diagnostics().await;
+        self.publish_graph_delta().await;
+
+        if let Some((previous, old_connections)) = before_save {
+            for path in analyzed.iter().filter(|path| saved.contains(*path)) {
+                self.notify_signature_changes(path, &previous, &old_connections).await;
+            }
+        }
+        moved
+    }
+
+    /// Compara las firmas de un archivo guardado con su análisis anterior (`previous`) y avisa al
+    /// frontend (`ShowFilesToChange`) qué archivos llaman a las funciones que cambiaron.
+    async fn notify_signature_changes(
+        &self,
+        path: &PathBuf,
+        previous: &HashMap<PathBuf, FileAnalysis>,
+        old_connections: &[Connections],
+    ) {
+        let Some(analysis) = self.store.read().await.get(path).cloned() else {
+            return;
         };
+        let changes = utils::detect_function_changes(path, &analysis, previous);
+        for (_, files) in utils::affected_files_by_change(&changes, old_connections) {
+            if !files.is_empty() {
+                self.client
+                    .send_notification::<ShowFilesToChange>(ShowFilesToChangePayload { files })
+                    .await;
+            }
+        }
+    }
 
-        // Procesar clases
-        let classes = binding
-            .get("classes")
-            .and_then(|v| v.as_array())
-            .expect("classes no es un array");
-
-        for class in classes {
-            let methods = class
-                .get("methods")
-                .and_then(|v| v.as_array())
-                .expect("methods no es un array");
-
-            for method in methods {
-                let function_calls = method
-                    .get("function_calls")
-                    .and_then(|v| v.as_array())
-                    .expect("function_calls no es un array");
-                let local_variables = method
-                    .get("local_variables")
-                    .and_then(|v| v.as_array())
-                    .cloned()
-                    .unwrap_or_default();
-                let method_parameters = method
-                    .get("parameters")
-                    .and_then(|v| v.as_array())
-                    .cloned()
-                    .unwrap_or_default();
+    /// Retorna el contenido del buffer abierto de `path` sólo si difiere del contenido en disco.
+    async fn unsaved_buffer(&self, path: &Path) -> Option<String> {
+        let text = {
+            let docs = self.open_documents.read().await;
+            docs.get(path)?.text.clone()
+        };
+        match fs::read_to_string(path).await {
+            Ok(on_disk) if on_disk == text => None,
+            _ => Some(text),
+        }
+    }
 
-                let new_connections = process_function_calls(
-                    function_calls,
-                    &local_variables,
-                    &method_parameters,
-                    &path_string,
-                    &imports_hashmap,
-                );
+    /// Texto actual de `path`: el buffer abierto si lo hay, si no el disco.
+    async fn document_text(&self, path: &Path) -> Option<String> {
+        let open = {
+            let docs = self.open_documents.read().await;
+            docs.get(path).map(|doc| doc.text.clone())
+        };
+        match open {
+            Some(text) => Some(text),
+            None => fs::read_to_string(path).await.ok(),
+        }
+    }
 
-                let mut guard = self.connections.write().await;
-                guard.extend(new_connections);
+    /// Arma el `LineIndex` de `paths` para traducir columnas entre bytes (analizador) y UTF-16 (LSP).
+    async fn line_index(&self, paths: Vec<PathBuf>) -> navigation::LineIndex {
+        let mut index = navigation::LineIndex::default();
+        for path in paths {
+            if index.contains(&path) {
+                continue;
+            }
+            if let Some(text) = self.document_text(&path).await {
+                index.insert(path, &text);
             }
         }
+        index
+    }
 
-        // Procesar funciones top-level
-        let functions = binding
-            .get("functions")
-            .and_then(|v| v.as_array())
-            .expect("functions no es un array");
-
-        for func in functions {
-            let function_calls = func
-                .get("function_calls")
-                .and_then(|v| v.as_array())
-                .expect("function_calls no es un array");
-            let local_variables = func
-                .get("local_variables")
-                .and_then(|v| v.as_array())
-                .cloned()
-                .unwrap_or_default();
-            let func_parameters = func
-                .get("parameters")
-                .and_then(|v| v.as_array())
-                .cloned()
-                .unwrap_or_default();
+    /// Traduce la posición del cursor que manda el editor (UTF-16) a columnas en bytes.
+    async fn cursor_position(&self, path: &Path, position: Position) -> Position {
+        self.line_index(vec![path.to_path_buf()]).await.decode_position(path, position)
+    }
 
-            let new_connections = process_function_calls(
-                function_calls,
-                &local_variables,
-                &func_parameters,
-                &path_string,
-                &imports_hashmap,
-            );
+    /// Traduce las columnas en bytes de `locations` a UTF-16 para responder al editor.
+    async fn encode_locations(&self, locations: Vec<Location>) -> Vec<Location> {
+        let lines = self
+            .line_index(locations.iter().filter_map(|l| l.uri.to_file_path().ok()).collect())
+            .await;
+        locations.into_iter().map(|l| lines.encode_location(l)).collect()
+    }
 
-            let mut guard = self.connections.write().await;
-            guard.extend(new_connections);
+    /// Lleva a bytes la posición de un `CallHierarchyItem` que devolvimos antes en UTF-16.
+    async fn decode_item(&self, mut item: CallHierarchyItem) -> CallHierarchyItem {
+        if let Ok(path) = item.uri.to_file_path() {
+            let lines = self.line_index(vec![path.clone()]).await;
+            item.selection_range.start = lines.decode_position(&path, item.selection_range.start);
         }
+        item
     }
 
-    async fn save_functions(&self, original_path: &Path, value: &Value) {
-        let binding = value.clone();
-        let path_string = original_path.to_str().unwrap().to_string();
+    /// Ubicaciones (columnas en bytes) de los `from m import f` que importan la función top-level
+    /// `definition`. Los métodos no se importan por nombre.
+    async fn import_sites(&self, definition: &FunctionsInFiles) -> Vec<Location> {
+        if definition.symbol.class_name.is_some() {
+            return vec![];
+        }
+        // (importador, nombre del módulo tal como lo escribe)
+        let importers: Vec<(PathBuf, String)> = {
+            let store = self.store.read().await;
+            store
+                .iter()
+                .flat_map(|(path, analysis)| {
+                    analysis
+                        .imports
+                        .iter()
+                        .filter(|i| i.path.as_deref() == Some(definition.symbol.module.as_str()))
+                        .map(|i| (path.clone(), i.name.clone()))
+                })
+                .collect()
+        };
 
+        let mut locations = vec![];
+        for (importer, module) in importers {
+            let Some(text) = self.document_text(&importer).await else {
+                continue;
+            };
+            let Ok(uri) = Url::from_file_path(&importer) else {
+                continue;
+            };
+            for range in navigation::imported_name_ranges(&text, &module, &definition.symbol.name) {
+                let location = Location { uri: uri.clone(), range };
+                if !locations.contains(&location) {
+                    locations.push(location);
+                }
+            }
+        }
+        locations
+    }
+
+    /// Error de rename que lista los call sites con más de un destino posible, o `None` si no hay.
+    async fn ambiguous_rename_error(&self, definition: &FunctionsInFiles) -> Option<String> {
+        let root = { self.workspace_root.read().await.clone() };
+        let connections = self.connections.read().await;
+        let ambiguous = navigation::ambiguous_call_sites(&connections, definition);
+        if ambiguous.is_empty() {
+            return None;
+        }
+        let sites: Vec<String> = ambiguous
+            .iter()
+            .map(|c| format!("{}:{}", graph::relativize(&c.file_use, &root), c.line))
+            .collect();
+        Some(format!(
+            "Cannot rename '{}': these call sites may also target another symbol: {}",
+            definition.symbol.name,
+            sites.join(", ")
+        ))
+    }
+
+    /// Analiza `path` con Tree-sitter. Si está abierto con cambios sin guardar, analiza el buffer
+    /// (escrito en un archivo sombra bajo `.lsp-analysis/buffers`, que se borra al terminar) en
+    /// lugar del disco. Retorna el resultado del analizador y si provino de un buffer sin guardar.
+    async fn analyze_file(&self, path: &Path) -> (std::result::Result<String, String>, bool) {
+        let root = { self.workspace_root.read().await.clone() };
+
+        let mut target = path.to_path_buf();
+        let from_buffer = match self.unsaved_buffer(path).await {
+            Some(text) => {
+                // Espejamos el path relativo para que el archivo sombra conserve su estructura de paquetes
+                let relative = match path.strip_prefix(&root) {
+                    Ok(relative) => relative.to_path_buf(),
+                    Err(_) => PathBuf::from(path.file_name().unwrap_or_default()),
+                };
+                let shadow = buffers_root_for_workspace(&root).join(relative);
+                let written = match shadow.parent() {
+                    Some(parent) => ensure_dirs(parent).await.and(fs::write(&shadow, text).await),
+                    None => fs::write(&shadow, text).await,
+                };
+                if let Err(e) = written {
+                    return (Err(format!("Cannot write buffer snapshot: {e}")), true);
+                }
+                target = shadow;
+                true
+            }
+            None => false,
+        };
+
+        let analyzed = target.clone();
+        let result = tokio::task::spawn_blocking(move || run_analysis(&analyzed, &[root]))
+            .await
+            .unwrap_or(Err("spawn_blocking failed".to_string()));
+        if from_buffer {
+            let _ = fs::remove_file(&target).await;
+        }
+        (result, from_buffer)
+    }
+
+    /// Elimina por completo un archivo borrado: store, índice de dependencias, caché en disco,
+    /// sus funciones y sus llamadas. El caller re-resuelve a sus dependientes (así sus llamadas
+    /// hacia él quedan `dangling`), publica diagnósticos y notifica al frontend.
+    async fn forget_file(&self, path: &Path) {
+        let path_string = path.to_string_lossy().to_string();
+        {
+            let mut guard = self.store.write().await;
+            guard.remove(path);
+        }
+        {
+            let mut deleted = self.deleted_modules.write().await;
+            deleted.insert(path.to_path_buf());
+        }
+        {
+            let mut index = self.dependency_index.write().await;
+            index.remove(path);
+        }
+        {
+            let mut hashes = self.content_hashes.write().await;
+            hashes.remove(path);
+        }
+        {
+            let mut quarantine = self.quarantine.write().await;
+            quarantine.release(path);
+        }
         {
             let mut f_in_files = self.functions_in_file.write().await;
-            f_in_files.retain(|c| c.file_src != path_string);
-        }
-
-        let calsses = binding
-            .get("classes")
-            .and_then(|v| v.as_array())
-            .expect("classes no es un array");
-
-        for calss in calsses {
-            let methods = calss
-                .get("methods")
-                .and_then(|v| v.as_array())
-                .expect("methods no es un array");
-
-            for method in methods {
-                if let Some(function_name) = method.get("name").and_then(|v| v.as_str()) {
-                    let line = method.get("line").and_then(|v| v.as_i64()).unwrap_or(1);
-                    let name_start_col = method.get("name_start_col").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
-                    let name_end_col = method.get("name_end_col").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
-
-                    let functions_in_file = FunctionsInFiles {
-                        file_src: path_string.clone(),
-                        function: function_name.to_string(),
-                        line,
-                        name_start_col,
-                        name_end_col,
-                    };
+            f_in_files.retain(|f| f.symbol.module != path_string);
+        }
+        {
+            let mut connections = self.connections.write().await;
+            connections.retain(|c| c.file_use != path_string);
+        }
 
-                    let mut guard = self.functions_in_file.write().await;
-                    guard.push(functions_in_file);
+        let root = { self.workspace_root.read().await.clone() };
+        let base = cache_root_for_workspace(&root);
+        let file_id = hash_path(path);
+        let target = base.join(format!("{file_id}.json"));
+        let _ = tokio::fs::remove_file(target).await;
+
+        if let Ok(uri) = Url::from_file_path(path) {
+            self.client.publish_diagnostics(uri, vec![], None).await;
+        }
+    }
+
+    /// Registra el hash del contenido analizado de `path`.
+    async fn remember_content_hash(&self, path: &Path, content_hash: &str) {
+        let mut hashes = self.content_hashes.write().await;
+        hashes.insert(path.to_path_buf(), content_hash.to_string());
+    }
+
+    /// Migra todo el estado de un archivo (o carpeta) movido de `old` a `new` sin re-analizar:
+    /// store, hashes, índice de dependencias, caché en disco, funciones y connections. Los imports
+    /// ya resueltos de otros archivos que apuntaban a `old` pasan a apuntar a `new`. No publica:
+    /// el lote que lo llama emite una única actualización. Retorna los archivos migrados.
+    async fn migrate_file(&self, old: &Path, new: &Path) -> Vec<(PathBuf, PathBuf)> {
+        let old_string = old.to_string_lossy().to_string();
+        let remap_string = |s: &str| -> Option<String> {
+            moves::remap_path(Path::new(s), old, new).map(|p| p.to_string_lossy().to_string())
+        };
+
+        let moved: Vec<(PathBuf, PathBuf)> = {
+            let mut store = self.store.write().await;
+            let moved: Vec<(PathBuf, PathBuf)> = store
+                .keys()
+                .filter_map(|p| Some((p.clone(), moves::remap_path(p, old, new)?)))
+                .collect();
+            for (from, to) in &moved {
+                if let Some(analysis) = store.remove(from) {
+                    store.insert(to.clone(), analysis);
                 }
             }
+            // Reescribimos los paths de imports resueltos que apuntaban al archivo movido
+            for import in store.values_mut().flat_map(|analysis| analysis.imports.iter_mut()) {
+                if let Some(new_path) = import.path.as_deref().and_then(remap_string) {
+                    import.path = Some(new_path);
+                }
+            }
+            moved
+        };
+        if moved.is_empty() {
+            return moved;
+        }
+        {
+            let mut deleted = self.deleted_modules.write().await;
+            for (_, to) in &moved {
+                deleted.remove(to);
+            }
         }
 
-        let functions = binding
-            .get("functions")
-            .and_then(|v| v.as_array())
-            .expect("functions no es un array");
+        {
+            let mut hashes = self.content_hashes.write().await;
+            for (from, to) in &moved {
+                if let Some(hash) = hashes.remove(from) {
+                    hashes.insert(to.clone(), hash);
+                }
+            }
+        }
+        {
+            let mut quarantine = self.quarantine.write().await;
+            quarantine.migrate(old, new);
+        }
+        {
+            let store = self.store.read().await;
+            let mut index = self.dependency_index.write().await;
+            for (from, _) in &moved {
+                index.remove(from);
+            }
+            for (path, analysis) in store.iter() {
+                index.update(path, analysis);
+            }
+        }
+        {
+            let mut f_in_files = self.functions_in_file.write().await;
+            for f in f_in_files.iter_mut() {
+                if let Some(to) = remap_string(&f.symbol.module) {
+                    f.symbol.module = to;
+                }
+            }
+        }
+        {
+            let mut connections = self.connections.write().await;
+            for c in connections.iter_mut() {
+                if let Some(to) = remap_string(&c.target.module) {
+                    c.target.module = to;
+                    c.dangling = false;
+                }
+                if let Some(to) = remap_string(&c.file_use) {
+                    c.file_use = to;
+                    c.caller.module = c.file_use.clone();
+                }
+            }
+        }
 
-        for function in functions {
-            if let Some(function_name) = function.get("name").and_then(|v| v.as_str()) {
-                let line = function.get("line").and_then(|v| v.as_i64()).unwrap_or(1);
-                let name_start_col = function.get("name_start_col").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
-                let name_end_col = function.get("name_end_col").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
+        let root = { self.workspace_root.read().await.clone() };
+        let base = cache_root_for_workspace(&root);
+        for (from, to) in &moved {
+            migrate_cache_entry(&base, from, to).await;
+            if let Ok(uri) = Url::from_file_path(from) {
+                self.client.publish_diagnostics(uri, vec![], None).await;
+            }
+        }
 
-                let functions_in_file = FunctionsInFiles {
-                    file_src: path_string.clone(),
-                    function: function_name.to_string(),
-                    line,
-                    name_start_col,
-                    name_end_col,
-                };
+        self.client
+            .log_message(
+                MessageType::INFO,
+                format!("Moved {} -> {} ({} files migrated)", old_string, new.display(), moved.len()),
+            )
+            .await;
+        moved
+    }
+
+    /// Arma el `WorkspaceEdit` que reescribe, en cada archivo que importa `old`, los imports del
+    /// módulo viejo por el del nuevo path. Lee el contenido actual de cada importer desde disco.
+    async fn import_rewrite_edit(&self, old: &Path, new: &Path) -> Option<WorkspaceEdit> {
+        let root = { self.workspace_root.read().await.clone() };
+        let old_module = moves::module_name(&root, old)
+            .or_else(|| moves::module_name(&root, &old.join("__init__.py")))?;
+        let new_module = moves::module_name(&root, new)
+            .or_else(|| moves::module_name(&root, &new.join("__init__.py")))?;
+        if old_module == new_module {
+            return None;
+        }
+
+        let importers: Vec<PathBuf> = {
+            let index = self.dependency_index.read().await;
+            let mut importers = index.importers_of(old);
+            importers.extend(index.importers_of(new));
+            importers.sort();
+            importers.dedup();
+            importers
+        };
+
+        let mut changes: HashMap<Url, Vec<TextEdit>> = HashMap::new();
+        for importer in importers {
+            let Ok(text) = fs::read_to_string(&importer).await else {
+                continue;
+            };
+            let edits = moves::import_rewrite_edits(&text, &old_module, &new_module);
+            if edits.is_empty() {
+                continue;
+            }
+            if let Ok(uri) = Url::from_file_path(&importer) {
+                changes.insert(uri, edits);
+            }
+        }
+
+        if changes.is_empty() {
+            return None;
+        }
+        Some(WorkspaceEdit {
+            changes: Some(changes),
+            ..Default::default()
+        })
+    }
+
+    /// Empareja eventos DELETED + CREATED con el mismo `content_hash` como moves de archivo.
+    /// Retorna los pares `(viejo, nuevo)` y los eventos restantes para procesar normalmente.
+    async fn pair_moves(
+        &self,
+        changes: Vec<(PathBuf, FileChangeType)>,
+    ) -> (Vec<(PathBuf, PathBuf)>, Vec<(PathBuf, FileChangeType)>) {
+        let deleted_hashes: Vec<(PathBuf, String)> = {
+            let hashes = self.content_hashes.read().await;
+            changes
+                .iter()
+                .filter(|(_, typ)| *typ == FileChangeType::DELETED)
+                .filter_map(|(p, _)| Some((p.clone(), hashes.get(p)?.clone())))
+                .collect()
+        };
+        if deleted_hashes.is_empty() {
+            return (vec![], changes);
+        }
+
+        let mut moves_found: Vec<(PathBuf, PathBuf)> = vec![];
+        for (path, typ) in &changes {
+            if *typ != FileChangeType::CREATED {
+                continue;
+            }
+            let Ok(bytes) = fs::read(path).await else {
+                continue;
+            };
+            let hash = hash_content(&bytes);
+            let already_paired = |old: &PathBuf| moves_found.iter().any(|(o, _)| o == old);
+            if let Some((old, _)) = deleted_hashes
+                .iter()
+                .find(|(old, h)| *h == hash && !already_paired(old))
+            {
+                moves_found.push((old.clone(), path.clone()));
+            }
+        }
+
+        let rest = changes
+            .into_iter()
+            .filter(|(p, _)| !moves_found.iter().any(|(old, new)| old == p || new == p))
+            .collect();
+        (moves_found, rest)
+    }
+
+    /// Ofrece al usuario reescribir los imports afectados por un move detectado vía watchers.
+    async fn offer_import_rewrite(&self, old: &Path, new: &Path) {
+        let Some(edit) = self.import_rewrite_edit(old, new).await else {
+            return;
+        };
+        let files = edit.changes.as_ref().map(|c| c.len()).unwrap_or(0);
+        let action = MessageActionItem {
+            title: "Update imports".to_string(),
+            properties: HashMap::new(),
+        };
+        let choice = self
+            .client
+            .show_message_request(
+                MessageType::INFO,
+                format!("{} was moved. Update imports in {} file(s)?", old.display(), files),
+                Some(vec![action.clone()]),
+            )
+            .await;
+        if let Ok(Some(selected)) = choice
+            && selected.title == action.title
+        {
+            let _ = self.client.apply_edit(edit).await;
+        }
+    }
+
+    /// Publica los diagnósticos de todo el workspace (funciones sin uso y llamadas a módulos
+    /// borrados). Los archivos del store sin diagnósticos se publican vacíos para limpiarlos.
+    async fn publish_workspace_diagnostics(&self) {
+        let root = { self.workspace_root.read().await.clone() };
+        let mut by_file = {
+            let connections = self.connections.read().await;
+            let functions_in_file = self.functions_in_file.read().await;
+            let unused_functions = utils::find_unused_functions(&functions_in_file, &connections);
+            let quarantine = self.quarantine.read().await;
+            diagnostics::workspace_diagnostics(&unused_functions, &connections, quarantine.files(), &root)
+        };
+
+        let paths: Vec<String> = {
+            let store = self.store.read().await;
+            store.keys().map(|p| p.to_string_lossy().to_string()).collect()
+        };
+        for path in paths {
+            by_file.entry(path).or_default();
+        }
 
-                let mut guard = self.functions_in_file.write().await;
-                guard.push(functions_in_file);
+        let lines = self
+            .line_index(
+                by_file
+                    .iter()
+                    .filter(|(_, d)| !d.is_empty())
+                    .map(|(file, _)| PathBuf::from(file))
+                    .collect(),
+            )
+            .await;
+        for (file, diagnostics) in by_file {
+            let path = PathBuf::from(&file);
+            let diagnostics = diagnostics
+                .into_iter()
+                .map(|d| Diagnostic { range: lines.encode_range(&path, d.range), ..d })
+                .collect();
+            if let Ok(uri) = Url::from_file_path(&file) {
+                self.client.publish_diagnostics(uri, diagnostics, None).await;
+            }
+        }
+    }
+
+    /// Guarda/actualiza el análisis del archivo en el store en memoria.
+    async fn upsert_store_value(&self, original_path: &Path, analysis: &FileAnalysis) {
+        {
+            let mut guard = self.store.write().await;
+            guard.insert(original_path.to_path_buf(), analysis.clone());
+        }
+        {
+            let mut deleted = self.deleted_modules.write().await;
+            deleted.remove(original_path);
+        }
+        let mut index = self.dependency_index.write().await;
+        index.update(original_path, analysis);
+    }
+
+    /// Registra en el log los problemas no fatales del análisis de `path` (ver `FileAnalysis::warnings`).
+    async fn log_analysis_warnings(&self, path: &Path, analysis: &FileAnalysis) {
+        let warnings = analysis.warnings();
+        if warnings.is_empty() {
+            return;
+        }
+        self.client
+            .log_message(
+                MessageType::WARNING,
+                format!("Analysis of {}: {}", path.display(), warnings.join("; ")),
+            )
+            .await;
+    }
+
+    /// Pone `path` en cuarentena porque su análisis falló o no es válido. El archivo conserva en el
+    /// store su último análisis válido (si lo tenía); el caller publica los diagnósticos.
+    async fn quarantine_file(&self, path: &Path, reason: String) {
+        let message = format!("Quarantined {}: {}", path.display(), reason);
+        let is_new = {
+            let mut quarantine = self.quarantine.write().await;
+            quarantine.isolate(path, reason)
+        };
+        // Sólo el primer fallo es un error visible; los siguientes (mientras se edita) van al log
+        let level = if is_new { MessageType::ERROR } else { MessageType::LOG };
+        self.client.log_message(level, message).await;
+    }
+
+    /// Saca a `path` de la cuarentena tras un análisis válido.
+    async fn release_file(&self, path: &Path) {
+        let mut quarantine = self.quarantine.write().await;
+        if quarantine.release(path) {
+            drop(quarantine);
+            self.client
+                .log_message(MessageType::INFO, format!("Released {} from quarantine", path.display()))
+                .await;
+        }
+    }
+
+    /// Request custom `lsp-server/status`: archivos analizados, llamadas resueltas y archivos en cuarentena.
+    async fn status(&self) -> Result<ServerStatus> {
+        let analyzed_files = self.store.read().await.len();
+        let connections = self.connections.read().await.len();
+        let quarantined = self.quarantine.read().await.files().cloned().collect();
+        Ok(ServerStatus {
+            analyzed_files,
+            connections,
+            quarantined,
+        })
+    }
+
+    /// Re-resuelve las `Connections` de `paths` contra un único snapshot del store (y una única
+    /// jerarquía de clases) y reemplaza las anteriores de esos archivos. Los que ya no están en el
+    /// store sólo pierden sus connections.
+    async fn resolve_paths(&self, paths: &[PathBuf]) {
+        // Se resuelve con el store tomado y se suelta antes de escribir las connections
+        let resolved: Vec<Connections> = {
+            let store = self.store.read().await;
+            let deleted = self.deleted_modules.read().await;
+            let hierarchy = ClassHierarchy::new(&store);
+            paths
+                .iter()
+                .filter_map(|path| Some(resolve_connections(path, store.get(path)?, &store, &hierarchy, &deleted)))
+                .flatten()
+                .collect()
+        };
+
+        let files: HashSet<String> = paths.iter().map(|p| p.to_string_lossy().to_string()).collect();
+        let mut connections = self.connections.write().await;
+        connections.retain(|c| !files.contains(&c.file_use));
+        connections.extend(resolved);
+    }
+
+    /// Segunda fase del análisis: resuelve las `Connections` de todos los archivos contra un único
+    /// snapshot del store completo, en orden estable de paths. El resultado no depende del orden
+    /// en que se recorrió el workspace.
+    async fn resolve_all_connections(&self) {
+        let all_connections: Vec<Connections> = {
+            let store = self.store.read().await;
+            let deleted = self.deleted_modules.read().await;
+            let hierarchy = ClassHierarchy::new(&store);
+            let mut paths: Vec<&PathBuf> = store.keys().collect();
+            paths.sort();
+            paths
+                .into_iter()
+                .flat_map(|path| resolve_connections(path, &store[path], &store, &hierarchy, &deleted))
+                .collect()
+        };
+
+        let mut connections = self.connections.write().await;
+        *connections = all_connections;
+    }
+
+    /// Archivos que dependen de `changed_paths`: los que los importan (transitivamente cuando una
+    /// cadena de `return_type` pasa por el importer), los que definen subclases o atributos tipados
+    /// con sus clases (y quienes importan esos módulos) y los que ya tenían connections resueltas
+    /// hacia ellos. Re-resolverlos hace que sus llamadas reflejen las clases, MROs y tipos de retorno
+    /// actuales. Los propios `changed_paths` no se incluyen.
+    async fn dependents_of(&self, changed_paths: &[PathBuf]) -> Vec<PathBuf> {
+        let mut dependents: Vec<PathBuf> = {
+            let store = self.store.read().await;
+            // Un importer sólo propaga el cambio si expone algún return_type (puede ser eslabón de una cadena)
+            let exposes_return_types =
+                |path: &Path| -> bool { store.get(path).is_some_and(FileAnalysis::exposes_return_types) };
+            let index = self.dependency_index.read().await;
+            let mut dependents: Vec<PathBuf> = changed_paths
+                .iter()
+                .flat_map(|changed| index.dependents_of(changed, exposes_return_types))
+                .collect();
+
+            // `Product().save()` en un módulo que sólo importa el de `Product` cambia si cambia su base
+            for module in ClassHierarchy::new(&store).class_dependents(changed_paths) {
+                dependents.extend(index.importers_of(&module));
+                dependents.push(module);
             }
+            dependents
+        };
+        {
+            let changed: Vec<String> = changed_paths
+                .iter()
+                .map(|p| p.to_string_lossy().to_string())
+                .collect();
+            let connections = self.connections.read().await;
+            dependents.extend(
+                connections
+                    .iter()
+                    .filter(|c| changed.contains(&c.target.module))
+                    .map(|c| PathBuf::from(&c.file_use)),
+            );
         }
+        dependents.sort();
+        dependents.dedup();
+        dependents.retain(|p| !changed_paths.contains(p));
+        dependents
+    }
+
+    /// Reemplaza las definiciones (funciones y métodos) registradas para `original_path`.
+    async fn save_functions(&self, original_path: &Path, analysis: &FileAnalysis) {
+        let path_string = original_path.to_string_lossy().to_string();
+
+        let methods = analysis.classes.iter().flat_map(|class| {
+            class.methods.iter().map(|method| {
+                (SymbolId::method(&path_string, &class.name, &method.name), method)
+            })
+        });
+        let functions = analysis
+            .functions
+            .iter()
+            .map(|function| (SymbolId::function(&path_string, &function.name), function));
+        let definitions: Vec<FunctionsInFiles> = methods
+            .chain(functions)
+            .map(|(symbol, function)| FunctionsInFiles {
+                symbol,
+                line: function.line,
+                name_start_col: function.name_start_col,
+                name_end_col: function.name_end_col,
+            })
+            .collect();
+
+        let mut f_in_files = self.functions_in_file.write().await;
+        f_in_files.retain(|f| f.symbol.module != path_string);
+        f_in_files.extend(definitions);
     }
 
     /// Persiste el resultado (con metadatos) a `<workspace>/.lsp-analysis/files/<hash>.json`.
     async fn persist_analysis_json(
         &self,
         original_path: &Path,
-        raw_json: &Value,
+        analysis: &FileAnalysis,
         content_hash: &str,
     ) -> std::io::Result<PathBuf> {
         // 1) Leemos el workspace root guardado en initialize
@@ -923,26 +2095,29 @@ impl Backend {
         let target = base.join(format!("{file_id}.json"));
 
         // 4) Envolvemos con metadatos y escribimos atómico
-        let wrapped = wrap_with_metadata(original_path, raw_json.clone(), content_hash);
+        let data = serde_json::to_value(analysis).map_err(std::io::Error::other)?;
+        let wrapped = wrap_with_metadata(original_path, data, content_hash);
         write_json_atomic(&target, &wrapped).await?;
 
         Ok(target)
     }
 
     /// Intenta cargar el análisis desde caché en disco.
-    /// Retorna `Some(data)` si el caché existe y el `content_hash` coincide con el del archivo actual.
+    /// Retorna `Some(data)` si el caché existe, el `content_hash` coincide con el del archivo actual
+    /// y los datos son un análisis válido (una entrada corrupta cuenta como cache miss).
     async fn try_load_from_cache(
         &self,
         original_path: &Path,
         current_content_hash: &str,
-    ) -> Option<Value> {
+    ) -> Option<FileAnalysis> {
         let root = { self.workspace_root.read().await.clone() };
         let base = cache_root_for_workspace(&root);
         let cache_path = base.join(format!("{}.json", hash_path(original_path)));
 
         let raw = fs::read_to_string(&cache_path).await.ok()?;
         let cached: Value = serde_json::from_str(&raw).ok()?;
-        validate_cache_entry(&cached, original_path, current_content_hash)
+        let data = validate_cache_entry(&cached, original_path, current_content_hash)?;
+        FileAnalysis::from_value(data).ok()
     }
 
     /// Elimina entradas de caché cuyo `original_path` ya no existe en disco.
@@ -964,11 +2139,18 @@ impl Backend {
             .to_string_lossy()
             .to_string();
 
-        // 1. Buscar la definición
+        // 1. Buscar la definición: por su símbolo si el frontend lo manda, si no por archivo y nombre
+        let symbol = params
+            .symbol_id
+            .as_deref()
+            .and_then(|id| SymbolId::from_node_id(id, &root));
         let definition = {
             let guard = self.functions_in_file.read().await;
             guard.iter()
-                .find(|f| f.function == *old_name && f.file_src == abs_file_path)
+                .find(|f| match &symbol {
+                    Some(symbol) => f.symbol == *symbol,
+                    None => f.symbol.name == *old_name && f.symbol.module == abs_file_path,
+                })
                 .cloned()
         };
 
@@ -980,11 +2162,19 @@ impl Backend {
             });
         };
 
+        if let Some(error) = self.ambiguous_rename_error(&def).await {
+            return Ok(RenameResult {
+                success: false,
+                error: Some(error),
+                files_edited: None,
+            });
+        }
+
         // 2. Recopilar todos los call sites que apuntan a esta definición
         let call_sites: Vec<Connections> = {
             let guard = self.connections.read().await;
-            guard.iter()
-                .filter(|c| c.function == *old_name && c.file_src == def.file_src)
+            navigation::renamed_call_sites(&guard, &def)
+                .into_iter()
                 .cloned()
                 .collect()
         };
@@ -996,7 +2186,7 @@ impl Backend {
 
         // Definición
         edits
-            .entry(def.file_src.clone())
+            .entry(def.symbol.module.clone())
             .or_default()
             .entry(def.line as usize)
             .or_default()
@@ -1012,6 +2202,19 @@ impl Backend {
                 .push((call.start_col, call.end_col));
         }
 
+        // `from m import f` en los archivos que importan la función
+        for site in self.import_sites(&def).await {
+            let Ok(path) = site.uri.to_file_path() else {
+                continue;
+            };
+            edits
+                .entry(path.to_string_lossy().to_string())
+                .or_default()
+                .entry(site.range.start.line as usize + 1)
+                .or_default()
+                .push((site.range.start.character as usize, site.range.end.character as usize));
+        }
+
         // 4. Aplicar edits en cada archivo
         let mut files_edited: Vec<String> = vec![];
 
@@ -1085,12 +2288,37 @@ impl LanguageServer for Backend {
             let mut guard = self.workspace_root.write().await;
             *guard = resolved_root.clone();
         }
+        {
+            let mut guard = self.analysis_workers.write().await;
+            *guard = analysis_workers_from_options(params.initialization_options.as_ref());
+        }
 
         Ok(InitializeResult {
             capabilities: ServerCapabilities {
                 text_document_sync: Some(TextDocumentSyncCapability::Kind(
                     TextDocumentSyncKind::FULL,
                 )),
+                definition_provider: Some(OneOf::Left(true)),
+                hover_provider: Some(HoverProviderCapability::Simple(true)),
+                document_symbol_provider: Some(OneOf::Left(true)),
+                code_lens_provider: Some(CodeLensOptions {
+                    resolve_provider: Some(false),
+                }),
+                workspace_symbol_provider: Some(OneOf::Left(true)),
+                references_provider: Some(OneOf::Left(true)),
+                call_hierarchy_provider: Some(CallHierarchyServerCapability::Simple(true)),
+                workspace: Some(WorkspaceServerCapabilities {
+                    workspace_folders: None,
+                    file_operations: Some(WorkspaceFileOperationsServerCapabilities {
+                        will_rename: Some(python_file_operation_registration()),
+                        did_rename: Some(python_file_operation_registration()),
+                        ..Default::default()
+                    }),
+                }),
+                rename_provider: Some(OneOf::Right(RenameOptions {
+                    prepare_provider: Some(true),
+                    work_done_progress_options: Default::default(),
+                })),
                 ..Default::default()
             },
             ..Default::default()
@@ -1104,7 +2332,9 @@ impl LanguageServer for Backend {
             .await;
         self.register_fs_watchers().await;
         self.reload_ignore_list().await;
-        self.analyze_workspace().await;
+        // El escaneo corre en su propia tarea (cancelable); los lotes del worker esperan a que termine
+        self.start_workspace_scan();
+        tokio::spawn(self.clone().run_analysis_worker());
     }
 
     /// Manejador LSP `shutdown`: termina el servidor limpiamente.
@@ -1112,12 +2342,67 @@ impl LanguageServer for Backend {
         Ok(())
     }
 
-    /// Manejador LSP `didSave`: re-analiza el archivo guardado, detecta cambios en firmas,
-    /// notifica al frontend y publica diagnósticos de funciones sin uso.
-    async fn did_save(&self, params: DidSaveTextDocumentParams) {
-        let uri = params.text_document.uri;
-        let path = uri.to_file_path().unwrap_or_default();
+    /// Manejador LSP `didOpen`: empieza a trackear el buffer del documento.
+    async fn did_open(&self, params: DidOpenTextDocumentParams) {
+        let Ok(path) = params.text_document.uri.to_file_path() else {
+            return;
+        };
+        let mut docs = self.open_documents.write().await;
+        docs.insert(
+            path,
+            OpenDocument {
+                text: params.text_document.text,
+                version: params.text_document.version,
+            },
+        );
+    }
+
+    /// Manejador LSP `didChange`: actualiza el buffer y lo encola para re-analizar el contenido
+    /// en pantalla (sin esperar al guardado).
+    async fn did_change(&self, params: DidChangeTextDocumentParams) {
+        let Ok(path) = params.text_document.uri.to_file_path() else {
+            return;
+        };
+        // Sync FULL: el último cambio trae el texto completo del documento
+        let Some(change) = params.content_changes.into_iter().last() else {
+            return;
+        };
+        let version = params.text_document.version;
+        {
+            let mut docs = self.open_documents.write().await;
+            // Ignoramos cambios que lleguen fuera de orden respecto al buffer ya registrado
+            if docs.get(&path).is_some_and(|doc| doc.version >= version) {
+                return;
+            }
+            docs.insert(path.clone(), OpenDocument { text: change.text, version });
+        }
+
+        // La cola hace el debounce: mientras se sigue escribiendo, los cambios se coalescen
+        self.enqueue_changes(vec![(path, FileChangeType::CHANGED)]).await;
+    }
+
+    /// Manejador LSP `didClose`: deja de trackear el buffer. Si quedaron cambios sin guardar,
+    /// vuelve a analizar el contenido en disco.
+    async fn did_close(&self, params: DidCloseTextDocumentParams) {
+        let Ok(path) = params.text_document.uri.to_file_path() else {
+            return;
+        };
+        let had_unsaved_changes = self.unsaved_buffer(&path).await.is_some();
+        {
+            let mut docs = self.open_documents.write().await;
+            docs.remove(&path);
+        }
+        if had_unsaved_changes && path.exists() {
+            self.enqueue_changes(vec![(path, FileChangeType::CHANGED)]).await;
+        }
+    }
 
+    /// Manejador LSP `didSave`: encola el archivo guardado. Al procesar el lote, el worker detecta
+    /// cambios de firma y avisa al frontend qué archivos los llaman.
+    async fn did_save(&self, params: DidSaveTextDocumentParams) {
+        let Ok(path) = params.text_document.uri.to_file_path() else {
+            return;
+        };
         if !path.exists() {
             self.client
                 .show_message(MessageType::ERROR, "File not found")
@@ -1125,172 +2410,389 @@ impl LanguageServer for Backend {
             return;
         }
 
-        // Si se modificó .lspignore, recargar la lista y salir
-        let root = { self.workspace_root.read().await.clone() };
-        if path == root.join(".lspignore") {
-            self.reload_ignore_list().await;
-            return;
-        }
+        // El watcher también informa el guardado (CHANGED): ambos eventos se coalescen en la cola
+        self.analysis_queue.lock().await.saved.insert(path.clone());
+        self.enqueue_changes(vec![(path, FileChangeType::CHANGED)]).await;
+    }
 
-        // Saltear archivos en carpetas ignoradas
-        {
-            let ignored = self.ignored_folders.read().await;
-            if is_ignored(&path, &ignored) {
-                return;
+    /// Manejador LSP `textDocument/definition`: ubica el call site bajo el cursor en `connections`
+    /// y salta a la definición registrada en `functions_in_file`.
+    async fn goto_definition(
+        &self,
+        params: GotoDefinitionParams,
+    ) -> Result<Option<GotoDefinitionResponse>> {
+        let position_params = params.text_document_position_params;
+        let Ok(path) = position_params.text_document.uri.to_file_path() else {
+            return Ok(None);
+        };
+        let path_string = path.to_string_lossy().to_string();
+        let position = self.cursor_position(&path, position_params.position).await;
+
+        let locations = {
+            let connections = self.connections.read().await;
+            let functions_in_file = self.functions_in_file.read().await;
+            let store = self.store.read().await;
+
+            // Todos los destinos del call site: cada alternativa de una unión, o el `__init__` y la clase
+            let mut locations: Vec<Location> = vec![];
+            for connection in navigation::connections_at(&connections, &path_string, position) {
+                let location = match navigation::definition_for(&functions_in_file, connection) {
+                    Some(definition) => navigation::definition_location(definition),
+                    None => store
+                        .get(Path::new(&connection.target.module))
+                        .and_then(|analysis| analysis.class(&connection.target.name))
+                        .filter(|_| connection.target.kind == symbol::SymbolKind::Class)
+                        .and_then(|class| navigation::class_location(&connection.target, class)),
+                };
+                if let Some(location) = location
+                    && !locations.contains(&location)
+                {
+                    locations.push(location);
+                }
             }
+            locations
+        };
+
+        if locations.is_empty() {
+            return Ok(None);
         }
+        Ok(Some(GotoDefinitionResponse::Array(self.encode_locations(locations).await)))
+    }
 
-        let path_clone = path.clone();
-        let root_clone = root.clone();
-        let analysis_result =
-            tokio::task::spawn_blocking(move || run_analysis(&path_clone, &[root_clone])).await;
-        match analysis_result.unwrap_or(Err("spawn_blocking failed".to_string())) {
-            Ok(json_str) => {
-                // 1) Parseamos a Value (si falla, guardamos algo neutro)
-                let value: serde_json::Value = match serde_json::from_str(&json_str) {
-                    Ok(v) => v,
-                    Err(_) => serde_json::json!({ "raw": json_str }),
-                };
+    /// Manejador LSP `textDocument/references`: desde una definición o un call site, retorna
+    /// todos los call sites resueltos en el workspace (y la definición si `includeDeclaration`).
+    async fn references(&self, params: ReferenceParams) -> Result<Option<Vec<Location>>> {
+        let position_params = params.text_document_position;
+        let Ok(path) = position_params.text_document.uri.to_file_path() else {
+            return Ok(None);
+        };
+        let path_string = path.to_string_lossy().to_string();
+        let position = self.cursor_position(&path, position_params.position).await;
+
+        let locations = {
+            let connections = self.connections.read().await;
+            let functions_in_file = self.functions_in_file.read().await;
+            let Some(definition) =
+                navigation::symbol_at(&connections, &functions_in_file, &path_string, position)
+            else {
+                return Ok(None);
+            };
 
-                let old_version: HashMap<PathBuf, Value> = {
-                    let read_guard = self.store.read().await;
-                    read_guard.clone()
-                };
+            let mut locations: Vec<Location> = vec![];
+            if params.context.include_declaration {
+                locations.extend(navigation::definition_location(definition));
+            }
+            locations.extend(
+                navigation::references_to(&connections, definition)
+                    .into_iter()
+                    .filter_map(navigation::call_site_location),
+            );
+            locations
+        };
 
-                let old_connections: Vec<Connections> = {
-                    let read_guard = self.connections.read().await;
-                    read_guard.clone()
-                };
+        Ok(Some(self.encode_locations(locations).await))
+    }
 
-                // 2) Actualizamos el store en memoria
-                self.upsert_store_value(&path, &value).await;
-                self.save_function_reference(&path, &value).await;
-                self.save_functions(&path, &value).await;
-
-                let changed_functions_firms: Vec<utils::FunctionChange> =
-                    utils::detect_function_changes(&path, &value, &old_version);
-                let files_to_warn = utils::affected_files_by_change(
-                    &changed_functions_firms,
-                    &old_connections,
-                    &path,
-                );
-                {
-                    let map = self.store.read().await;
-                    let current_connections = self.connections.read().await;
-                    let functions_in_file_lock = self.functions_in_file.read().await;
-                    let unused_functions: Vec<FunctionsInFiles> =
-                        utils::find_unused_functions(&functions_in_file_lock, &current_connections);
-                    let message = format_for_lsp_message(map, root.clone());
-
-                    self.client
-                        .send_notification::<ProcessedJson>(ProcessedJsonPayload { files: message })
-                        .await;
-                    if files_to_warn.len() > 0 {
-                        for (_, files) in files_to_warn {
-                            if files.len() > 0 {
-                                self.client
-                                    .send_notification::<ShowFilesToChange>(
-                                        ShowFilesToChangePayload { files: files },
-                                    )
-                                    .await;
-                            }
-                        }
-                    } 
-                    if unused_functions.len() > 0 {
-                        {
-                            let store = self.store.read().await;
-                            for path in store.keys() {
-                                if let Ok(uri) = Url::from_file_path(path) {
-                                    self.client.publish_diagnostics(uri, vec![], None).await;
-                                }
-                            }
-                        }
-                        
-                        let mut seen = HashSet::new();
-                        let unused_functions: Vec<FunctionsInFiles> = unused_functions
-                            .into_iter()
-                            .filter(|f| seen.insert((f.file_src.clone(), f.function.clone(), f.line)))
-                            .collect();
-
-                        let mut by_file: HashMap<String, Vec<FunctionsInFiles>> = HashMap::new();
-                        for f in &unused_functions {
-                            by_file
-                                .entry(f.file_src.clone())
-                                .or_default()
-                                .push(f.clone());
-                        }
+    /// Manejador LSP `textDocument/prepareRename`: valida que el cursor esté sobre una función
+    /// conocida y retorna el rango del nombre junto con el nombre actual como placeholder.
+    async fn prepare_rename(
+        &self,
+        params: TextDocumentPositionParams,
+    ) -> Result<Option<PrepareRenameResponse>> {
+        let Ok(path) = params.text_document.uri.to_file_path() else {
+            return Ok(None);
+        };
+        let path_string = path.to_string_lossy().to_string();
+        let lines = self.line_index(vec![path.clone()]).await;
+        let position = lines.decode_position(&path, params.position);
+
+        let connections = self.connections.read().await;
+        let functions_in_file = self.functions_in_file.read().await;
+        if navigation::is_instantiation_at(&connections, &path_string, position) {
+            return Ok(None);
+        }
+        let Some(definition) =
+            navigation::symbol_at(&connections, &functions_in_file, &path_string, position)
+        else {
+            return Ok(None);
+        };
+        let Some(range) =
+            navigation::symbol_range_at(&connections, &functions_in_file, &path_string, position)
+        else {
+            return Ok(None);
+        };
 
-                        eprintln!("{:#?}", by_file);
-
-                        for (file_src, functions) in by_file {
-                            let diagnostics: Vec<Diagnostic> = functions
-                                .iter()
-                                .map(|f| Diagnostic {
-                                    range: Range {
-                                        start: Position {
-                                            line: f.line as u32 - 1,
-                                            character: 0,
-                                        },
-                                        end: Position {
-                                            line: f.line as u32 - 1,
-                                            character: u32::MAX,
-                                        },
-                                    },
-                                    severity: Some(DiagnosticSeverity::WARNING),
-                                    message: format!("Function '{}' is defined but never used", f.function),
-                                    source: Some("lsp-backend".to_string()),
-                                    ..Default::default()
-                                })
-                                .collect();
-
-                            let uri = Url::from_file_path(file_src).unwrap();
-
-                            self.client
-                                .publish_diagnostics(uri, diagnostics, None)
-                                .await;
-                        }
-                    }
-                }
+        Ok(Some(PrepareRenameResponse::RangeWithPlaceholder {
+            range: lines.encode_range(&path, range),
+            placeholder: definition.symbol.name.clone(),
+        }))
+    }
 
-                // 3) Persistimos a disco (manejo de error no fatal)
-                let file_bytes = fs::read(&path).await.unwrap_or_default();
-                let content_hash = hash_content(&file_bytes);
-                match self.persist_analysis_json(&path, &value, &content_hash).await {
-                    Ok(written) => {
-                        self.client
-                            .log_message(
-                                MessageType::INFO,
-                                format!("Analysis persisted: {}", written.display()),
-                            )
-                            .await;
-                        self.client
-                            .show_message(MessageType::INFO, "Analysis complete & persisted")
-                            .await;
-                    }
-                    Err(e) => {
-                        self.client
-                            .log_message(MessageType::ERROR, format!("Persist failed: {}", e))
-                            .await;
-                        self.client
-                            .show_message(
-                                MessageType::WARNING,
-                                "Analysis complete (persist failed)",
-                            )
-                            .await;
-                    }
-                }
+    /// Manejador LSP `textDocument/rename`: retorna un `WorkspaceEdit` con la definición y todos
+    /// sus call sites, para que el editor lo aplique sobre sus buffers (y quede en su undo).
+    async fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
+        if !navigation::is_valid_identifier(&params.new_name) {
+            return Err(tower_lsp::jsonrpc::Error::invalid_params(format!(
+                "'{}' is not a valid Python identifier",
+                params.new_name
+            )));
+        }
 
-                // (Más adelante) acá podríamos reconstruir y enviar el grafo global.
-            }
-            Err(err) => {
-                self.client
-                    .show_message(MessageType::ERROR, format!("Analyzer failed: {}", err))
-                    .await;
+        let position_params = params.text_document_position;
+        let Ok(path) = position_params.text_document.uri.to_file_path() else {
+            return Ok(None);
+        };
+        let path_string = path.to_string_lossy().to_string();
+        let position = self.cursor_position(&path, position_params.position).await;
+
+        let definition = {
+            let connections = self.connections.read().await;
+            let functions_in_file = self.functions_in_file.read().await;
+            if navigation::is_instantiation_at(&connections, &path_string, position) {
+                return Ok(None);
             }
+            let Some(definition) =
+                navigation::symbol_at(&connections, &functions_in_file, &path_string, position)
+            else {
+                return Ok(None);
+            };
+            definition.clone()
+        };
+
+        // Un call site con varios destinos no se puede reescribir sin romper las otras alternativas
+        if let Some(error) = self.ambiguous_rename_error(&definition).await {
+            return Err(tower_lsp::jsonrpc::Error::invalid_params(error));
         }
+
+        let import_sites = self.import_sites(&definition).await;
+        let mut edit = {
+            let connections = self.connections.read().await;
+            navigation::rename_edit(&connections, &definition, &import_sites, &params.new_name)
+        };
+
+        let changes = edit.changes.take().unwrap_or_default();
+        let lines = self.line_index(changes.keys().filter_map(|uri| uri.to_file_path().ok()).collect()).await;
+        let changes = changes
+            .into_iter()
+            .map(|(uri, edits)| {
+                let path = uri.to_file_path().unwrap_or_default();
+                let edits = edits
+                    .into_iter()
+                    .map(|e| TextEdit { range: lines.encode_range(&path, e.range), ..e })
+                    .collect();
+                (uri, edits)
+            })
+            .collect();
+        edit.changes = Some(changes);
+        Ok(Some(edit))
+    }
+
+    /// Manejador LSP `textDocument/prepareCallHierarchy`: retorna el item de la función o método
+    /// bajo el cursor (definición o call site).
+    async fn prepare_call_hierarchy(
+        &self,
+        params: CallHierarchyPrepareParams,
+    ) -> Result<Option<Vec<CallHierarchyItem>>> {
+        let position_params = params.text_document_position_params;
+        let Ok(path) = position_params.text_document.uri.to_file_path() else {
+            return Ok(None);
+        };
+        let path_string = path.to_string_lossy().to_string();
+        let position = self.cursor_position(&path, position_params.position).await;
+
+        let item = {
+            let connections = self.connections.read().await;
+            let functions_in_file = self.functions_in_file.read().await;
+            navigation::symbol_at(&connections, &functions_in_file, &path_string, position)
+                .and_then(navigation::call_hierarchy_item)
+        };
+        let Some(item) = item else {
+            return Ok(None);
+        };
+        let lines = self.line_index(item.uri.to_file_path().into_iter().collect()).await;
+        Ok(Some(vec![lines.encode_item(item)]))
+    }
+
+
+    /// Manejador LSP `callHierarchy/incomingCalls`: funciones que llaman al item, según `connections`.
+    async fn incoming_calls(
+        &self,
+        params: CallHierarchyIncomingCallsParams,
+    ) -> Result<Option<Vec<CallHierarchyIncomingCall>>> {
+        let item = self.decode_item(params.item).await;
+        let calls = {
+            let connections = self.connections.read().await;
+            let functions_in_file = self.functions_in_file.read().await;
+            let Some(definition) = navigation::definition_for_item(&functions_in_file, &item)
+            else {
+                return Ok(None);
+            };
+            navigation::incoming_calls(&connections, &functions_in_file, definition)
+        };
+
+        // Los rangos de cada llamada están en el archivo del llamador
+        let lines = self
+            .line_index(calls.iter().filter_map(|c| c.from.uri.to_file_path().ok()).collect())
+            .await;
+        Ok(Some(
+            calls
+                .into_iter()
+                .map(|call| {
+                    let path = call.from.uri.to_file_path().unwrap_or_default();
+                    CallHierarchyIncomingCall {
+                        from_ranges: call.from_ranges.into_iter().map(|r| lines.encode_range(&path, r)).collect(),
+                        from: lines.encode_item(call.from),
+                    }
+                })
+                .collect(),
+        ))
+    }
+
+    /// Manejador LSP `callHierarchy/outgoingCalls`: funciones llamadas desde el item, según `connections`.
+    async fn outgoing_calls(
+        &self,
+        params: CallHierarchyOutgoingCallsParams,
+    ) -> Result<Option<Vec<CallHierarchyOutgoingCall>>> {
+        let item = self.decode_item(params.item).await;
+        let calls = {
+            let connections = self.connections.read().await;
+            let functions_in_file = self.functions_in_file.read().await;
+            let Some(definition) = navigation::definition_for_item(&functions_in_file, &item)
+            else {
+                return Ok(None);
+            };
+            navigation::outgoing_calls(&connections, &functions_in_file, definition)
+        };
+
+        // Los rangos de cada llamada están en el archivo del item; los destinos, en los suyos
+        let caller = item.uri.to_file_path().unwrap_or_default();
+        let lines = self
+            .line_index(
+                calls
+                    .iter()
+                    .filter_map(|c| c.to.uri.to_file_path().ok())
+                    .chain([caller.clone()])
+                    .collect(),
+            )
+            .await;
+        Ok(Some(
+            calls
+                .into_iter()
+                .map(|call| CallHierarchyOutgoingCall {
+                    from_ranges: call.from_ranges.into_iter().map(|r| lines.encode_range(&caller, r)).collect(),
+                    to: lines.encode_item(call.to),
+                })
+                .collect(),
+        ))
+    }
+
+    /// Manejador LSP `textDocument/hover`: muestra la firma completa, el archivo que la define,
+    /// un extracto del docstring y la cantidad de llamadas entrantes/salientes resueltas.
+    async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
+        let position_params = params.text_document_position_params;
+        let Ok(path) = position_params.text_document.uri.to_file_path() else {
+            return Ok(None);
+        };
+        let path_string = path.to_string_lossy().to_string();
+        let root = { self.workspace_root.read().await.clone() };
+        let lines = self.line_index(vec![path.clone()]).await;
+        let position = lines.decode_position(&path, position_params.position);
+
+        let connections = self.connections.read().await;
+        let functions_in_file = self.functions_in_file.read().await;
+        let Some(definition) =
+            navigation::symbol_at(&connections, &functions_in_file, &path_string, position)
+        else {
+            return Ok(None);
+        };
+
+        let store = self.store.read().await;
+        let Some(func) = store
+            .get(&PathBuf::from(&definition.symbol.module))
+            .and_then(|analysis| hover::find_function(analysis, definition))
+        else {
+            return Ok(None);
+        };
+
+        let relative_file = graph::relativize(&definition.symbol.module, &root);
+        let incoming = navigation::references_to(&connections, definition).len();
+        let outgoing = navigation::calls_from(&connections, definition).len();
+
+        Ok(Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: hover::hover_markdown(definition, func, &relative_file, incoming, outgoing),
+            }),
+            range: navigation::symbol_range_at(&connections, &functions_in_file, &path_string, position)
+                .map(|range| lines.encode_range(&path, range)),
+        }))
+    }
+
+    /// Manejador LSP `textDocument/documentSymbol`: outline jerárquico (clases → métodos, luego funciones).
+    async fn document_symbol(
+        &self,
+        params: DocumentSymbolParams,
+    ) -> Result<Option<DocumentSymbolResponse>> {
+        let Ok(path) = params.text_document.uri.to_file_path() else {
+            return Ok(None);
+        };
+        let symbols = {
+            let store = self.store.read().await;
+            let Some(analysis) = store.get(&path) else {
+                return Ok(None);
+            };
+            symbols::document_symbols(analysis)
+        };
+        let lines = self.line_index(vec![path.clone()]).await;
+        Ok(Some(DocumentSymbolResponse::Nested(
+            symbols.into_iter().map(|symbol| lines.encode_symbol(&path, symbol)).collect(),
+        )))
+    }
+
+    /// Manejador LSP `workspace/symbol`: búsqueda fuzzy de funciones y métodos en todo el workspace.
+    async fn symbol(
+        &self,
+        params: WorkspaceSymbolParams,
+    ) -> Result<Option<Vec<SymbolInformation>>> {
+        let functions_in_file = self.functions_in_file.read().await;
+        Ok(Some(symbols::workspace_symbols(&functions_in_file, &params.query)))
+    }
+
+    /// Manejador LSP `textDocument/codeLens`: muestra "N references · M files" sobre cada definición
+    /// del archivo y "unused" sobre las que reporta `utils::find_unused_functions`.
+    async fn code_lens(&self, params: CodeLensParams) -> Result<Option<Vec<CodeLens>>> {
+        let Ok(path) = params.text_document.uri.to_file_path() else {
+            return Ok(None);
+        };
+        let path_string = path.to_string_lossy().to_string();
+
+        // El archivo y los que llaman a sus funciones: los lens llevan rangos de ambos
+        let files: HashSet<PathBuf> = {
+            let connections = self.connections.read().await;
+            connections
+                .iter()
+                .filter(|c| c.target.module == path_string)
+                .map(|c| PathBuf::from(&c.file_use))
+                .chain([path.clone()])
+                .collect()
+        };
+        let lines = self.line_index(files.into_iter().collect()).await;
+
+        let connections = self.connections.read().await;
+        let functions_in_file = self.functions_in_file.read().await;
+        let unused = utils::find_unused_functions(&functions_in_file, &connections);
+
+        Ok(Some(navigation::code_lenses(
+            &connections,
+            &functions_in_file,
+            &unused,
+            &path_string,
+            &lines,
+        )))
     }
 
-    /// Manejador LSP `didChangeWatchedFiles`: procesa en paralelo todos los eventos de cambio recibidos.
+    /// Manejador LSP `didChangeWatchedFiles`: encola los eventos; la cola los coalesce y los
+    /// procesa como un único lote (un `git checkout` produce una sola actualización).
     async fn did_change_watched_files(&self, params: DidChangeWatchedFilesParams) {
         let changes: Vec<(PathBuf, FileChangeType)> = params
             .changes
@@ -1301,11 +2803,46 @@ impl LanguageServer for Backend {
             })
             .collect();
 
-        let futs: Vec<_> = changes
-            .iter()
-            .map(|(p, typ)| self.process_path_change(p, *typ))
-            .collect();
-        futures::future::join_all(futs).await;
+        self.enqueue_changes(changes).await;
+    }
+
+    /// Manejador LSP `workspace/willRenameFiles`: antes de mover módulos, retorna el `WorkspaceEdit`
+    /// que reescribe los imports en todos los archivos que los importan.
+    async fn will_rename_files(&self, params: RenameFilesParams) -> Result<Option<WorkspaceEdit>> {
+        let mut changes: HashMap<Url, Vec<TextEdit>> = HashMap::new();
+        for file in params.files {
+            let (Some(old), Some(new)) = (uri_to_path(&file.old_uri), uri_to_path(&file.new_uri))
+            else {
+                continue;
+            };
+            if let Some(edit) = self.import_rewrite_edit(&old, &new).await {
+                for (uri, edits) in edit.changes.unwrap_or_default() {
+                    changes.entry(uri).or_default().extend(edits);
+                }
+            }
+        }
+
+        if changes.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(WorkspaceEdit {
+            changes: Some(changes),
+            ..Default::default()
+        }))
+    }
+
+    /// Manejador LSP `workspace/didRenameFiles`: encola los renames; el worker migra store, caché
+    /// e índices al nuevo path bajo el lock de lotes, sin carreras con el re-análisis.
+    async fn did_rename_files(&self, params: RenameFilesParams) {
+        {
+            let mut queue = self.analysis_queue.lock().await;
+            for file in params.files {
+                if let (Some(old), Some(new)) = (uri_to_path(&file.old_uri), uri_to_path(&file.new_uri)) {
+                    queue.renames.push((old, new));
+                }
+            }
+        }
+        self.analysis_signal.notify_one();
     }
 }
 
@@ -1313,15 +2850,13 @@ impl LanguageServer for Backend {
 async fn main() {
     eprintln!("Server is up and running");
 
-    let (service, socket) = LspService::build(|client| Backend {
-        client,
-        store: RwLock::new(HashMap::new()),
-        connections: RwLock::new(vec![]),
-        functions_in_file: RwLock::new(vec![]),
-        workspace_root: RwLock::new(std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."))),
-        ignored_folders: RwLock::new(vec![]),
-    })
+    let (service, socket) = LspService::build(Backend::new)
     .custom_method("lsp-server/renameFunction", Backend::rename_function)
+    .custom_method("lsp-server/cancelAnalysis", Backend::cancel_analysis_request)
+    .custom_method("lsp-server/rescanWorkspace", Backend::rescan_workspace)
+    .custom_method("lsp-server/graphSnapshot", Backend::graph_snapshot)
+    .custom_method("lsp-server/status", Backend::status)
+    .custom_method("window/workDoneProgress/cancel", Backend::work_done_progress_cancel)
     .finish();
     Server::new(tokio::io::stdin(), tokio::io::stdout(), socket)
         .serve(service)
@@ -1331,6 +2866,7 @@ async fn main() {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::model::LocalVariable;
     use std::io::Write;
 
     // ── hash_content ──────────────────────────────────────────────────────────
@@ -1416,6 +2952,373 @@ mod tests {
         assert!(validate_cache_entry(&cached, path, &hash).is_none());
     }
 
+    // ── resolve_connections ───────────────────────────────────────────────────
+
+    /// Workspace en memoria para probar `resolve_connections`: cada archivo es el JSON que produciría
+    /// el analizador, y se resuelve contra el store completo con su jerarquía de clases.
+    #[derive(Default)]
+    struct Workspace {
+        store: HashMap<PathBuf, FileAnalysis>,
+        deleted: HashSet<PathBuf>,
+    }
+
+    impl Workspace {
+        fn file(mut self, path: &str, analysis: Value) -> Self {
+            let analysis = FileAnalysis::from_value(analysis).unwrap();
+            self.store.insert(PathBuf::from(path), analysis);
+            self
+        }
+
+        fn resolve(&self, path: &str) -> Vec<Connections> {
+            let path = Path::new(path);
+            resolve_connections(path, &self.store[path], &self.store, &ClassHierarchy::new(&self.store), &self.deleted)
+        }
+
+        fn targets(&self, path: &str) -> Vec<SymbolId> {
+            self.resolve(path).into_iter().map(|c| c.target).collect()
+        }
+    }
+
+    /// Definición sin llamadas, para clases y módulos que sólo son destino.
+    fn def(name: &str, line: i64) -> Value {
+        json!({ "name": name, "line": line, "name_start_col": 4, "name_end_col": 4 + name.len(), "function_calls": [] })
+    }
+
+    /// `shop.py` con `Product.price` y `make_product() -> Product`; `main.py` los usa vía `import shop`.
+    fn shop_workspace() -> Workspace {
+        let mut make_product = def("make_product", 5);
+        make_product["return_type"] = json!("Product");
+        Workspace::default()
+            .file("/ws/shop.py", json!({
+                "imports": [],
+                "classes": [{ "name": "Product", "methods": [def("price", 2)] }],
+                "functions": [make_product]
+            }))
+            .file("/ws/main.py", json!({
+                "imports": [{ "name": "shop", "path": "/ws/shop.py" }],
+                "classes": [],
+                "functions": [{
+                    "name": "main", "line": 2, "name_start_col": 4, "name_end_col": 8,
+                    "local_variables": [{ "name": "p", "assigned_from": "make_product" }],
+                    "function_calls": [
+                        { "name": "make_product", "import_name": "shop", "line": 3, "start_col": 8, "end_col": 20 },
+                        { "name": "price", "object_name": "p", "line": 4, "start_col": 6, "end_col": 11 }
+                    ]
+                }]
+            }))
+    }
+
+    #[test]
+    fn resolve_connections_uses_the_complete_store() {
+        let mut workspace = shop_workspace();
+        let connections = workspace.resolve("/ws/main.py");
+        let resolved: Vec<(&SymbolId, &SymbolId)> =
+            connections.iter().map(|c| (&c.target, &c.caller)).collect();
+        let main = SymbolId::function("/ws/main.py", "main");
+        assert_eq!(
+            resolved,
+            vec![
+                (&SymbolId::function("/ws/shop.py", "make_product"), &main),
+                (&SymbolId::method("/ws/shop.py", "Product", "price"), &main),
+            ]
+        );
+
+        // Sin shop.py en el store y todavía sin analizar, la llamada no genera edge
+        let shop = workspace.store.remove(Path::new("/ws/shop.py")).unwrap();
+        assert!(workspace.resolve("/ws/main.py").is_empty());
+
+        // Si se borró, la llamada queda `dangling` y el método no resuelve
+        workspace.deleted.insert(PathBuf::from("/ws/shop.py"));
+        let connections = workspace.resolve("/ws/main.py");
+        assert_eq!(connections.len(), 1);
+        assert!(connections[0].dangling);
+        assert_eq!(connections[0].target, SymbolId::function("/ws/shop.py", "make_product"));
+
+        // Al volver a resolver con el archivo de vuelta en el store deja de estar `dangling`
+        workspace.deleted.remove(Path::new("/ws/shop.py"));
+        workspace.store.insert(PathBuf::from("/ws/shop.py"), shop);
+        assert!(workspace.resolve("/ws/main.py").iter().all(|c| !c.dangling));
+    }
+
+    #[test]
+    fn overlaid_buffer_imports_resolve_against_the_workspace() {
+        let mut workspace = shop_workspace();
+        let main_path = PathBuf::from("/ws/main.py");
+        let root = Path::new("/ws");
+        let previous = workspace.store[&main_path].clone();
+
+        // Analizado desde `/ws/.lsp-analysis/buffers/main.py`: `shop` resolvió dentro del espejo
+        // y un import relativo nuevo (`helpers`) no resolvió
+        let mut buffer = previous.clone();
+        buffer.imports = vec![
+            Import {
+                name: "shop".to_string(),
+                path: Some("/ws/.lsp-analysis/buffers/shop.py".to_string()),
+            },
+            Import { name: "helpers".to_string(), path: None },
+        ];
+        overlay_imports(&mut buffer, &buffers_root_for_workspace(root), root, Some(&previous));
+        assert_eq!(buffer.imports[0].path.as_deref(), Some("/ws/shop.py"));
+        assert_eq!(buffer.imports[1].path, None);
+
+        // Sin path en el espejo, el import conserva el que tenía en el análisis anterior
+        buffer.imports[0].path = None;
+        overlay_imports(&mut buffer, &buffers_root_for_workspace(root), root, Some(&previous));
+        assert_eq!(buffer.imports[0].path.as_deref(), Some("/ws/shop.py"));
+
+        workspace.store.insert(main_path, buffer);
+        assert!(workspace.targets("/ws/main.py").contains(&SymbolId::function("/ws/shop.py", "make_product")));
+    }
+
+    #[test]
+    fn resolve_connections_skips_unresolved_imports() {
+        let mut workspace = shop_workspace();
+        let main = workspace.store.get_mut(Path::new("/ws/main.py")).unwrap();
+        // `import os` (sin path) y un import resuelto a un archivo existente fuera del store
+        // (una librería, una carpeta ignorada) no producen connections
+        let vendored_file = tempfile::NamedTempFile::new().unwrap();
+        main.imports = vec![
+            Import { name: "os".to_string(), path: None },
+            Import {
+                name: "shop".to_string(),
+                path: Some(vendored_file.path().to_string_lossy().to_string()),
+            },
+        ];
+        main.functions[0].function_calls[0].import_name = Some("os".to_string());
+        let mut vendored = main.functions[0].function_calls[0].clone();
+        vendored.import_name = Some("shop".to_string());
+        main.functions[0].function_calls.push(vendored);
+
+        let connections = workspace.resolve("/ws/main.py");
+        assert!(connections.is_empty(), "{connections:?}");
+    }
+
+    /// `base.py` con `BaseModel.save`; `order.py` con `Order(base.BaseModel)` y llamadas a `self`/`cls`.
+    fn order_workspace() -> Workspace {
+        Workspace::default()
+            .file("/ws/base.py", json!({
+                "imports": [],
+                "classes": [{ "name": "BaseModel", "methods": [def("save", 2)] }],
+                "functions": []
+            }))
+            .file("/ws/order.py", json!({
+                "imports": [{ "name": "base", "path": "/ws/base.py" }],
+                "classes": [{ "name": "Order", "bases": ["base.BaseModel"], "methods": [
+                    def("build", 3),
+                    { "name": "submit", "line": 5, "name_start_col": 8, "name_end_col": 14, "function_calls": [
+                        { "name": "build", "object_name": "cls", "line": 6, "start_col": 12, "end_col": 17 },
+                        { "name": "save", "import_name": "self", "line": 7, "start_col": 13, "end_col": 17 }
+                    ]}
+                ]}],
+                "functions": [{
+                    "name": "process", "line": 10, "name_start_col": 4, "name_end_col": 11,
+                    "parameters": [{ "name": "order", "param_type": "Order" }],
+                    "function_calls": [{ "name": "save", "object_name": "order", "line": 11, "start_col": 10, "end_col": 14 }]
+                }]
+            }))
+    }
+
+    #[test]
+    fn resolve_connections_resolves_self_and_cls_calls_through_bases() {
+        assert_eq!(
+            order_workspace().targets("/ws/order.py"),
+            vec![
+                SymbolId::method("/ws/order.py", "Order", "build"),
+                SymbolId::method("/ws/base.py", "BaseModel", "save"),
+                // Método heredado sobre un parámetro tipado: se resuelve por el MRO de Order
+                SymbolId::method("/ws/base.py", "BaseModel", "save"),
+            ]
+        );
+    }
+
+    #[test]
+    fn resolve_connections_ignores_methods_missing_from_the_class_and_its_bases() {
+        let mut workspace = order_workspace();
+        let order = workspace.store.get_mut(Path::new("/ws/order.py")).unwrap();
+        let missing = |object_name: &str, line: i64| FunctionCall {
+            name: "missing".to_string(),
+            object_name: Some(object_name.to_string()),
+            line,
+            start_col: 13,
+            end_col: 20,
+            ..Default::default()
+        };
+        order.classes[0].methods[1].function_calls.push(missing("self", 8));
+        order.functions[0].function_calls.push(missing("order", 12));
+
+        // Ni Order ni BaseModel definen `missing`: ninguna arista, tampoco hacia la propia clase
+        let connections = workspace.resolve("/ws/order.py");
+        assert_eq!(connections.len(), 3);
+        assert!(connections.iter().all(|c| c.target.name != "missing"), "{connections:?}");
+    }
+
+    #[test]
+    fn resolve_connections_skips_a_shadowed_self() {
+        let mut workspace = order_workspace();
+        let submit = &mut workspace.store.get_mut(Path::new("/ws/order.py")).unwrap().classes[0].methods[1];
+        // `self = other` dentro del método: `self.save()` ya no es una llamada sobre la instancia
+        submit.local_variables.push(LocalVariable { name: "self".to_string(), ..Default::default() });
+
+        let targets = workspace.targets("/ws/order.py");
+        assert_eq!(
+            targets,
+            vec![
+                SymbolId::method("/ws/order.py", "Order", "build"),
+                SymbolId::method("/ws/base.py", "BaseModel", "save"),
+            ]
+        );
+        let from_submit = workspace.resolve("/ws/order.py").into_iter().filter(|c| c.caller.name == "submit").count();
+        assert_eq!(from_submit, 1);
+    }
+
+    #[test]
+    fn resolve_connections_links_instantiations_to_the_class_and_its_init() {
+        let workspace = Workspace::default()
+            .file("/ws/shop.py", json!({
+                "imports": [],
+                "classes": [
+                    { "name": "Model", "methods": [def("__init__", 2)] },
+                    { "name": "Product", "bases": ["Model"], "methods": [def("price", 6)] }
+                ],
+                "functions": []
+            }))
+            .file("/ws/main.py", json!({
+                "imports": [{ "name": "shop", "path": "/ws/shop.py" }],
+                "classes": [{ "name": "Cart", "methods": [] }],
+                "functions": [{
+                    "name": "main", "line": 3, "name_start_col": 4, "name_end_col": 8,
+                    "local_variables": [{ "name": "p", "assigned_from": "Product" }],
+                    "function_calls": [
+                        { "name": "Product", "import_name": "shop", "line": 4, "start_col": 13, "end_col": 20 },
+                        { "name": "price", "object_name": "p", "line": 5, "start_col": 6, "end_col": 11 },
+                        { "name": "Cart", "line": 6, "start_col": 4, "end_col": 8 }
+                    ]
+                }]
+            }));
+
+        let resolved: Vec<(SymbolId, EdgeKind)> = workspace
+            .resolve("/ws/main.py")
+            .into_iter()
+            .map(|c| (c.target, c.kind))
+            .collect();
+        assert_eq!(
+            resolved,
+            vec![
+                // shop.Product(): __init__ heredado de Model y la clase
+                (SymbolId::method("/ws/shop.py", "Model", "__init__"), EdgeKind::Instantiates),
+                (SymbolId::class("/ws/shop.py", "Product"), EdgeKind::Instantiates),
+                // p = shop.Product()  →  p.price() es Product.price
+                (SymbolId::method("/ws/shop.py", "Product", "price"), EdgeKind::Calls),
+                // Clase local sin __init__: sólo la clase
+                (SymbolId::class("/ws/main.py", "Cart"), EdgeKind::Instantiates),
+            ]
+        );
+    }
+
+    #[test]
+    fn resolve_connections_parses_unions_qualified_names_and_loop_variables() {
+        let mut latest = def("latest", 11);
+        latest["return_type"] = json!("typing.Optional['Order']");
+        let workspace = Workspace::default()
+            .file("/ws/shop.py", json!({
+                "imports": [],
+                "classes": [
+                    { "name": "Cart", "methods": [def("total", 2)] },
+                    { "name": "Order", "methods": [def("total", 5)] },
+                    { "name": "Product", "methods": [def("price", 8)] }
+                ],
+                "functions": [latest]
+            }))
+            .file("/ws/main.py", json!({
+                "imports": [{ "name": "shop", "path": "/ws/shop.py" }],
+                "classes": [],
+                "functions": [{
+                    "name": "report", "line": 3, "name_start_col": 4, "name_end_col": 10,
+                    "parameters": [
+                        { "name": "basket", "param_type": "Union[shop.Cart, 'shop.Order', None]" },
+                        { "name": "products", "param_type": "list[shop.Product]" }
+                    ],
+                    "local_variables": [{ "name": "p", "iterates_over": "products" }],
+                    "function_calls": [
+                        { "name": "total", "object_name": "basket", "line": 4, "start_col": 11, "end_col": 16 },
+                        { "name": "price", "object_name": "p", "line": 6, "start_col": 10, "end_col": 15 },
+                        { "name": "latest", "import_name": "shop", "line": 7, "start_col": 9, "end_col": 15 },
+                        { "name": "total", "chain_source_fn": "latest", "line": 7, "start_col": 18, "end_col": 23 }
+                    ]
+                }]
+            }));
+
+        assert_eq!(
+            workspace.targets("/ws/main.py"),
+            vec![
+                // Una arista por alternativa de la unión
+                SymbolId::method("/ws/shop.py", "Cart", "total"),
+                SymbolId::method("/ws/shop.py", "Order", "total"),
+                // for p in products  →  Product.price
+                SymbolId::method("/ws/shop.py", "Product", "price"),
+                SymbolId::function("/ws/shop.py", "latest"),
+                // shop.latest().total()  →  Optional['Order']
+                SymbolId::method("/ws/shop.py", "Order", "total"),
+            ]
+        );
+    }
+
+    #[test]
+    fn resolve_connections_types_instance_attributes_from_init_and_annotations() {
+        let workspace = Workspace::default()
+            .file("/ws/repos.py", json!({
+                "imports": [],
+                "classes": [
+                    { "name": "UserRepository", "methods": [def("find_user", 2)] },
+                    { "name": "Mailer", "methods": [def("send", 5)] },
+                    { "name": "Clock", "methods": [def("now", 8)] }
+                ],
+                "functions": []
+            }))
+            .file("/ws/service.py", json!({
+                "imports": [{ "name": "repos", "path": "/ws/repos.py" }],
+                "classes": [{
+                    "name": "UserService",
+                    "attributes": [{ "name": "clock", "annotation": "Optional[repos.Clock]" }],
+                    "methods": [
+                        { "name": "__init__", "line": 3, "name_start_col": 8, "name_end_col": 16,
+                          "parameters": ["self", { "name": "mailer", "param_type": "Mailer" }],
+                          "local_variables": [
+                              { "name": "self.repo", "assigned_from": "UserRepository" },
+                              { "name": "self.mailer", "assigned_from": "mailer" }
+                          ],
+                          "function_calls": [{ "name": "UserRepository", "import_name": "repos", "line": 4, "start_col": 26, "end_col": 40 }]
+                        },
+                        { "name": "notify", "line": 7, "name_start_col": 8, "name_end_col": 14, "function_calls": [
+                            { "name": "find_user", "object_name": "self.repo", "line": 8, "start_col": 24, "end_col": 33 },
+                            { "name": "send", "object_name": "self.mailer", "line": 9, "start_col": 20, "end_col": 24 },
+                            { "name": "now", "object_name": "self.clock", "line": 10, "start_col": 19, "end_col": 22 },
+                            { "name": "close", "object_name": "self.session", "line": 11, "start_col": 21, "end_col": 26 }
+                        ]}
+                    ]
+                }],
+                "functions": []
+            }));
+
+        let targets: Vec<SymbolId> = workspace
+            .resolve("/ws/service.py")
+            .into_iter()
+            .filter(|c| c.caller.name == "notify")
+            .map(|c| c.target)
+            .collect();
+        assert_eq!(
+            targets,
+            vec![
+                // self.repo = UserRepository()
+                SymbolId::method("/ws/repos.py", "UserRepository", "find_user"),
+                // self.mailer = mailer  (mailer: Mailer)
+                SymbolId::method("/ws/repos.py", "Mailer", "send"),
+                // clock: Optional[repos.Clock] en el cuerpo de la clase
+                SymbolId::method("/ws/repos.py", "Clock", "now"),
+            ]
+        );
+    }
+
     // ── cleanup_orphan_entries_in ─────────────────────────────────────────────
 
     // ── ciclo completo: persistir → warm-up → invalidar ──────────────────────
@@ -1471,6 +3374,30 @@ mod tests {
         assert_eq!(hit_after_update.unwrap(), new_data);
     }
 
+    // ── migrate_cache_entry ───────────────────────────────────────────────────
+
+    #[tokio::test]
+    async fn migrate_cache_entry_moves_entry_to_new_path_hash() {
+        let cache_dir = tempfile::tempdir().unwrap();
+        let old = Path::new("/workspace/projecto/shop.py");
+        let new = Path::new("/workspace/core/shop.py");
+        let hash = hash_content(b"def foo(): pass");
+
+        let old_entry = cache_dir.path().join(format!("{}.json", hash_path(old)));
+        let data = serde_json::json!({ "functions": [], "classes": [], "imports": [] });
+        write_json_atomic(&old_entry, &wrap_with_metadata(old, data.clone(), &hash))
+            .await
+            .unwrap();
+
+        migrate_cache_entry(cache_dir.path(), old, new).await;
+
+        let new_entry = cache_dir.path().join(format!("{}.json", hash_path(new)));
+        assert!(!old_entry.exists(), "la entrada vieja debe eliminarse");
+        let raw = tokio::fs::read_to_string(&new_entry).await.unwrap();
+        let cached: Value = serde_json::from_str(&raw).unwrap();
+        assert_eq!(validate_cache_entry(&cached, new, &hash), Some(data));
+    }
+
     // ── cleanup_orphan_entries_in ─────────────────────────────────────────────
 
     #[tokio::test]
@@ -1503,4 +3430,159 @@ mod tests {
         assert!(valid_cache.exists(), "el JSON válido debe conservarse");
         assert!(!orphan_cache.exists(), "el JSON huérfano debe eliminarse");
     }
+
+    #[test]
+    fn analysis_workers_reads_positive_option_or_falls_back() {
+        let options = json!({ "analysisWorkers": 3 });
+        assert_eq!(analysis_workers_from_options(Some(&options)), 3);
+
+        let fallback = std::thread::available_parallelism().map_or(1, |n| n.get());
+        let zero = json!({ "analysisWorkers": 0 });
+        assert_eq!(analysis_workers_from_options(Some(&zero)), fallback);
+        assert_eq!(analysis_workers_from_options(None), fallback);
+    }
+
+    // ── cola de re-análisis y cancelación ─────────────────────────────────────
+
+    #[tokio::test]
+    async fn enqueue_changes_only_queues_and_coalesces_by_path() {
+        let (service, _socket) = LspService::new(Backend::new);
+        let backend = service.inner().clone();
+        let a = PathBuf::from("/ws/a.py");
+        let b = PathBuf::from("/ws/b.py");
+
+        // Sin worker corriendo, encolar retorna enseguida y el lote queda pendiente
+        backend.enqueue_changes(vec![(a.clone(), FileChangeType::CHANGED)]).await;
+        backend
+            .enqueue_changes(vec![(a.clone(), FileChangeType::DELETED), (b.clone(), FileChangeType::CREATED)])
+            .await;
+
+        let queue = backend.analysis_queue.lock().await;
+        assert_eq!(queue.pending.len(), 2);
+        assert_eq!(queue.pending[&a], FileChangeType::DELETED);
+        assert_eq!(queue.pending[&b], FileChangeType::CREATED);
+    }
+
+    /// Workspace temporal con tres archivos `.py` y un backend apuntando a él. El socket se descarta:
+    /// los mensajes al cliente fallan enseguida en vez de quedar esperando.
+    async fn scan_fixture() -> (tempfile::TempDir, Backend) {
+        let workspace = tempfile::tempdir().unwrap();
+        for name in ["a.py", "b.py", "c.py"] {
+            std::fs::write(workspace.path().join(name), "def f():\n    pass\n").unwrap();
+        }
+        let (service, _) = LspService::new(Backend::new);
+        let backend = service.inner().clone();
+        *backend.workspace_root.write().await = workspace.path().to_path_buf();
+        (workspace, backend)
+    }
+
+    #[tokio::test]
+    async fn workspace_scan_runs_to_completion_without_cancel() {
+        let (_workspace, backend) = scan_fixture().await;
+        backend.start_workspace_scan().await.unwrap();
+        // El analizador de prueba falla siempre: cada archivo analizado termina en cuarentena
+        assert_eq!(backend.quarantine.read().await.files().count(), 3);
+    }
+
+    #[tokio::test]
+    async fn rescan_workspace_recovers_a_cancelled_scan() {
+        let (_workspace, backend) = scan_fixture().await;
+        let scan = backend.start_workspace_scan();
+        backend.cancel_analysis_request().await.unwrap();
+        scan.await.unwrap();
+        assert_eq!(backend.quarantine.read().await.files().count(), 0);
+
+        // El request no espera al escaneo: corre en su propia tarea
+        backend.rescan_workspace().await.unwrap();
+        for _ in 0..200 {
+            if backend.quarantine.read().await.files().count() == 3 {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("el nuevo escaneo debe analizar los tres archivos");
+    }
+
+    #[tokio::test]
+    async fn progress_summary_reports_analyzed_and_quarantined_files() {
+        let (service, _) = LspService::new(Backend::new);
+        let backend = service.inner();
+        let epoch = backend.analysis_epoch.load(Ordering::SeqCst);
+        assert_eq!(backend.progress_summary(epoch, 7, 0, 7), "Analyzed 7 files");
+        assert_eq!(backend.progress_summary(epoch, 5, 2, 7), "Analyzed 5 files (2 quarantined)");
+        backend.cancel_analysis();
+        assert_eq!(backend.progress_summary(epoch, 2, 1, 7), "Analysis cancelled after 3 of 7 files (1 quarantined)");
+    }
+
+    #[tokio::test]
+    async fn editor_renames_migrate_in_a_batch_and_drop_the_follow_up_watcher_events() {
+        let (workspace, backend) = scan_fixture().await;
+        let old = workspace.path().join("a.py");
+        let new = workspace.path().join("moved.py");
+        backend.store.write().await.insert(old.clone(), FileAnalysis::default());
+        std::fs::rename(&old, &new).unwrap();
+
+        // El handler sólo encola: la migración la hace el lote, bajo `batch_lock`
+        backend
+            .did_rename_files(RenameFilesParams {
+                files: vec![FileRename {
+                    old_uri: Url::from_file_path(&old).unwrap().to_string(),
+                    new_uri: Url::from_file_path(&new).unwrap().to_string(),
+                }],
+            })
+            .await;
+        let renames = std::mem::take(&mut backend.analysis_queue.lock().await.renames);
+        assert_eq!(renames, vec![(old.clone(), new.clone())]);
+        assert!(backend.store.read().await.contains_key(&old));
+
+        backend.process_batch(HashMap::new(), HashSet::new(), renames).await;
+        assert!(backend.store.read().await.contains_key(&new));
+
+        // Los eventos del watcher por el mismo rename no re-analizan ni olvidan el archivo
+        let watcher = HashMap::from([(old.clone(), FileChangeType::DELETED), (new.clone(), FileChangeType::CREATED)]);
+        backend.process_batch(watcher, HashSet::new(), vec![]).await;
+        assert!(backend.store.read().await.contains_key(&new));
+        assert_eq!(backend.quarantine.read().await.files().count(), 0);
+        assert!(backend.deleted_modules.read().await.is_empty());
+        assert!(backend.analysis_queue.lock().await.expected_events.is_empty());
+    }
+
+    #[tokio::test]
+    async fn analyzing_an_unsaved_buffer_removes_its_shadow_copy() {
+        let (workspace, backend) = scan_fixture().await;
+        let path = workspace.path().join("a.py");
+        backend
+            .open_documents
+            .write()
+            .await
+            .insert(path.clone(), OpenDocument { text: "def g():\n    pass\n".to_string(), version: 2 });
+
+        let (_, from_buffer) = backend.analyze_file(&path).await;
+        assert!(from_buffer);
+        let shadow = buffers_root_for_workspace(workspace.path()).join("a.py");
+        assert!(!shadow.exists(), "la copia del buffer no debe quedar en disco");
+    }
+
+    #[tokio::test]
+    async fn cancel_analysis_request_aborts_the_workspace_scan() {
+        let (_workspace, backend) = scan_fixture().await;
+        let scan = backend.start_workspace_scan();
+        backend.cancel_analysis_request().await.unwrap();
+        scan.await.unwrap();
+        assert_eq!(backend.quarantine.read().await.files().count(), 0);
+        assert!(backend.store.read().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn work_done_progress_cancel_aborts_the_workspace_scan() {
+        let (_workspace, backend) = scan_fixture().await;
+        let scan = backend.start_workspace_scan();
+        backend
+            .work_done_progress_cancel(WorkDoneProgressCancelParams {
+                token: NumberOrString::String("lsp-backend/analysis/1".to_string()),
+            })
+            .await;
+        scan.await.unwrap();
+        assert_eq!(backend.quarantine.read().await.files().count(), 0);
+    }
 }
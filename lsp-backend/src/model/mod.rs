@@ -0,0 +1,407 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+
+/// Resultado del analizador para un archivo: imports resueltos, clases y funciones top-level.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct FileAnalysis {
+    pub imports: Vec<Import>,
+    pub classes: Vec<ClassDef>,
+    pub functions: Vec<FunctionDef>,
+}
+
+/// Import de un archivo. `path` es el archivo (o paquete) al que resolvió; `None` si no resolvió.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Import {
+    pub name: String,
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct ClassDef {
+    pub name: String,
+    pub methods: Vec<FunctionDef>,
+    // Posición del nombre de la clase (versiones viejas del analizador no la informan)
+    #[serde(default)]
+    pub line: Option<i64>,
+    #[serde(default)]
+    pub name_start_col: usize,
+    #[serde(default)]
+    pub name_end_col: usize,
+    #[serde(default)]
+    pub end_line: Option<i64>,
+    // Clases base tal como aparecen en el código (`Base`, `models.Model`)
+    #[serde(default)]
+    pub bases: Vec<String>,
+    // Atributos declarados en el cuerpo de la clase (anotaciones y campos de dataclass)
+    #[serde(default)]
+    pub attributes: Vec<ClassAttribute>,
+}
+
+/// Atributo declarado a nivel de clase: `repo: UserRepository` o `price: float = 0.0`.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct ClassAttribute {
+    pub name: String,
+    #[serde(default)]
+    pub annotation: Option<String>,
+}
+
+/// Función top-level o método de una clase.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct FunctionDef {
+    pub name: String,
+    #[serde(default)]
+    pub parameters: Vec<Parameter>,
+    #[serde(default)]
+    pub return_type: Option<String>,
+    #[serde(default)]
+    pub function_calls: Vec<FunctionCall>,
+    #[serde(default)]
+    pub local_variables: Vec<LocalVariable>,
+    // Posición del nombre (versiones viejas del analizador no la informan: ver `FileAnalysis::warnings`)
+    #[serde(default = "first_line")]
+    pub line: i64,
+    #[serde(default)]
+    pub name_start_col: usize,
+    #[serde(default)]
+    pub name_end_col: usize,
+    #[serde(default)]
+    pub end_line: Option<i64>,
+    #[serde(default)]
+    pub docstring: Option<String>,
+}
+
+/// Parámetro de una función. El analizador puede mandarlo como objeto o como string plano (sólo el nombre).
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(from = "ParameterRepr")]
+pub struct Parameter {
+    pub name: String,
+    pub param_type: Option<String>,
+    pub default_value: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ParameterRepr {
+    Plain(String),
+    Full {
+        name: String,
+        #[serde(default)]
+        param_type: Option<String>,
+        #[serde(default)]
+        default_value: Option<String>,
+    },
+}
+
+impl From<ParameterRepr> for Parameter {
+    fn from(repr: ParameterRepr) -> Self {
+        match repr {
+            ParameterRepr::Plain(name) => Parameter {
+                name,
+                param_type: None,
+                default_value: None,
+            },
+            ParameterRepr::Full {
+                name,
+                param_type,
+                default_value,
+            } => Parameter {
+                name,
+                param_type,
+                default_value,
+            },
+        }
+    }
+}
+
+/// Llamada dentro de una función: `func()`, `module.func()`, `obj.method()` o un eslabón de cadena.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct FunctionCall {
+    pub name: String,
+    // Módulo importado del que sale la función (`module.func()` o `from module import func`)
+    #[serde(default)]
+    pub import_name: Option<String>,
+    // Receptor de la llamada en `obj.method()`
+    #[serde(default)]
+    pub object_name: Option<String>,
+    // Función cuyo resultado recibe la llamada en `a().b()`
+    #[serde(default)]
+    pub chain_source_fn: Option<String>,
+    #[serde(default = "first_line")]
+    pub line: i64,
+    #[serde(default)]
+    pub start_col: usize,
+    #[serde(default)]
+    pub end_col: usize,
+    #[serde(default)]
+    pub is_native: bool,
+}
+
+/// Variable local y la función de la que recibe su valor (`p = make_product()`). Las asignaciones
+/// a atributos (`self.repo = repo`) llegan con el nombre completo (`self.repo`).
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct LocalVariable {
+    pub name: String,
+    #[serde(default)]
+    pub assigned_from: Option<String>,
+    // Anotación explícita (`self.repo: UserRepository = ...`)
+    #[serde(default)]
+    pub annotation: Option<String>,
+    // Variable de un `for`: nombre de lo que recorre (`for p in products` → `products`)
+    #[serde(default)]
+    pub iterates_over: Option<String>,
+}
+
+// Línea por defecto cuando el analizador no informa la posición
+fn first_line() -> i64 {
+    1
+}
+
+/// Salida del analizador que no se puede usar.
+#[derive(Debug)]
+pub enum ModelError {
+    // No es JSON o no tiene la forma esperada
+    Malformed(serde_json::Error),
+    // La forma es correcta pero los datos son inconsistentes; `at` indica dónde (`functions[2].function_calls[0]`)
+    Invalid { at: String, reason: String },
+}
+
+impl fmt::Display for ModelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModelError::Malformed(e) => write!(f, "malformed analysis: {e}"),
+            ModelError::Invalid { at, reason } => write!(f, "invalid analysis at {at}: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for ModelError {}
+
+fn invalid(at: &str, reason: &str) -> ModelError {
+    ModelError::Invalid {
+        at: at.to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+fn require_name(name: &str, at: &str) -> Result<(), ModelError> {
+    if name.trim().is_empty() {
+        return Err(invalid(at, "empty name"));
+    }
+    Ok(())
+}
+
+// Un rango vacío en la columna 0 es lo que queda cuando el analizador no informó la posición
+fn missing_span(start_col: usize, end_col: usize) -> bool {
+    start_col == 0 && end_col == 0
+}
+
+fn require_span(line: i64, start_col: usize, end_col: usize, at: &str) -> Result<(), ModelError> {
+    if line < 1 {
+        return Err(invalid(at, &format!("line {line} is not 1-based")));
+    }
+    if end_col < start_col {
+        return Err(invalid(at, &format!("column range {start_col}..{end_col} is reversed")));
+    }
+    Ok(())
+}
+
+impl FileAnalysis {
+    /// Parsea y valida la salida JSON del analizador.
+    pub fn parse(json: &str) -> Result<FileAnalysis, ModelError> {
+        let analysis: FileAnalysis = serde_json::from_str(json).map_err(ModelError::Malformed)?;
+        analysis.validate()?;
+        Ok(analysis)
+    }
+
+    /// Igual que `parse`, a partir de un `Value` ya parseado (por ejemplo, el `data` de una entrada de caché).
+    pub fn from_value(value: Value) -> Result<FileAnalysis, ModelError> {
+        let analysis: FileAnalysis = serde_json::from_value(value).map_err(ModelError::Malformed)?;
+        analysis.validate()?;
+        Ok(analysis)
+    }
+
+    /// Verifica nombres no vacíos, líneas 1-based y rangos de columnas bien ordenados.
+    pub fn validate(&self) -> Result<(), ModelError> {
+        for (i, import) in self.imports.iter().enumerate() {
+            require_name(&import.name, &format!("imports[{i}]"))?;
+        }
+        for (i, class) in self.classes.iter().enumerate() {
+            let at = format!("classes[{i}]");
+            require_name(&class.name, &at)?;
+            for (j, attribute) in class.attributes.iter().enumerate() {
+                require_name(&attribute.name, &format!("{at}.attributes[{j}]"))?;
+            }
+            if let Some(line) = class.line {
+                require_span(line, class.name_start_col, class.name_end_col.max(class.name_start_col), &at)?;
+            }
+            for (j, method) in class.methods.iter().enumerate() {
+                method.validate(&format!("{at}.methods[{j}]"))?;
+            }
+        }
+        for (i, function) in self.functions.iter().enumerate() {
+            function.validate(&format!("functions[{i}]"))?;
+        }
+        Ok(())
+    }
+
+    /// Problemas que no impiden usar el análisis: hoy, definiciones y llamadas sin posición
+    /// (analizadores viejos). Ir a la definición o renombrar en ellas apunta al inicio de la línea.
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = vec![];
+        let mut check_function = |function: &FunctionDef, at: String| {
+            if missing_span(function.name_start_col, function.name_end_col) {
+                warnings.push(format!("{at}: missing name span"));
+            }
+            for (i, call) in function.function_calls.iter().enumerate() {
+                if missing_span(call.start_col, call.end_col) {
+                    warnings.push(format!("{at}.function_calls[{i}]: missing call span"));
+                }
+            }
+        };
+        for (i, class) in self.classes.iter().enumerate() {
+            for (j, method) in class.methods.iter().enumerate() {
+                check_function(method, format!("classes[{i}].methods[{j}]"));
+            }
+        }
+        for (i, function) in self.functions.iter().enumerate() {
+            check_function(function, format!("functions[{i}]"));
+        }
+        warnings
+    }
+
+    /// Función top-level llamada `name`.
+    pub fn function(&self, name: &str) -> Option<&FunctionDef> {
+        self.functions.iter().find(|f| f.name == name)
+    }
+
+    /// Clase llamada `name`.
+    pub fn class(&self, name: &str) -> Option<&ClassDef> {
+        self.classes.iter().find(|c| c.name == name)
+    }
+
+    /// `true` si alguna función o método declara `return_type` (el archivo puede ser eslabón de una cadena).
+    pub fn exposes_return_types(&self) -> bool {
+        self.functions
+            .iter()
+            .chain(self.classes.iter().flat_map(|c| &c.methods))
+            .any(|f| f.return_type.is_some())
+    }
+}
+
+impl ClassDef {
+    /// Método de la clase llamado `name`.
+    pub fn method(&self, name: &str) -> Option<&FunctionDef> {
+        self.methods.iter().find(|m| m.name == name)
+    }
+
+    /// Atributo declarado a nivel de clase llamado `name`.
+    pub fn attribute(&self, name: &str) -> Option<&ClassAttribute> {
+        self.attributes.iter().find(|a| a.name == name)
+    }
+}
+
+impl FunctionDef {
+    fn validate(&self, at: &str) -> Result<(), ModelError> {
+        require_name(&self.name, at)?;
+        require_span(self.line, self.name_start_col, self.name_end_col, at)?;
+        if let Some(end_line) = self.end_line
+            && end_line < self.line
+        {
+            return Err(invalid(at, &format!("end_line {end_line} is before line {}", self.line)));
+        }
+        for (i, call) in self.function_calls.iter().enumerate() {
+            let at = format!("{at}.function_calls[{i}]");
+            require_name(&call.name, &at)?;
+            require_span(call.line, call.start_col, call.end_col, &at)?;
+        }
+        Ok(())
+    }
+
+    /// Parámetro llamado `name`.
+    pub fn parameter(&self, name: &str) -> Option<&Parameter> {
+        self.parameters.iter().find(|p| p.name == name)
+    }
+
+    /// Variable local llamada `name`.
+    pub fn local_variable(&self, name: &str) -> Option<&LocalVariable> {
+        self.local_variables.iter().find(|v| v.name == name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_accepts_analyzer_output_with_plain_and_typed_parameters() {
+        let analysis = FileAnalysis::parse(
+            r#"{
+                "imports": [{ "name": "shop", "path": "/ws/shop.py" }, { "name": "os", "path": null }],
+                "classes": [{ "name": "Cart", "methods": [{
+                    "name": "add", "parameters": ["self", { "name": "item", "param_type": "Product" }],
+                    "return_type": null, "function_calls": [], "line": 3, "name_start_col": 8, "name_end_col": 11
+                }]}],
+                "functions": [{
+                    "name": "main", "parameters": [], "return_type": "None", "line": 9,
+                    "name_start_col": 4, "name_end_col": 8,
+                    "local_variables": [{ "name": "c", "assigned_from": "Cart" }],
+                    "function_calls": [{ "name": "add", "object_name": "c", "line": 10, "start_col": 6, "end_col": 9 }]
+                }]
+            }"#,
+        )
+        .unwrap();
+
+        let add = analysis.class("Cart").and_then(|c| c.method("add")).unwrap();
+        assert_eq!(add.parameters[0].name, "self");
+        assert_eq!(add.parameter("item").unwrap().param_type.as_deref(), Some("Product"));
+        assert_eq!(analysis.imports[1].path, None);
+        let main = analysis.function("main").unwrap();
+        assert_eq!(main.local_variable("c").unwrap().assigned_from.as_deref(), Some("Cart"));
+        assert_eq!(main.function_calls[0].object_name.as_deref(), Some("c"));
+        assert!(analysis.exposes_return_types());
+    }
+
+    #[test]
+    fn parse_reports_malformed_and_inconsistent_output() {
+        assert!(matches!(FileAnalysis::parse("not json"), Err(ModelError::Malformed(_))));
+        // `functions` no es un array
+        assert!(matches!(
+            FileAnalysis::parse(r#"{ "imports": [], "classes": [], "functions": {} }"#),
+            Err(ModelError::Malformed(_))
+        ));
+
+        let reversed = r#"{ "imports": [], "classes": [], "functions": [{
+            "name": "f", "function_calls": [{ "name": "g", "line": 2, "start_col": 9, "end_col": 4 }],
+            "line": 1, "name_start_col": 4, "name_end_col": 5
+        }] }"#;
+        let err = FileAnalysis::parse(reversed).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid analysis at functions[0].function_calls[0]: column range 9..4 is reversed"
+        );
+    }
+
+    #[test]
+    fn missing_spans_default_and_are_reported_as_warnings() {
+        // Salida de un analizador viejo: sin posiciones ni `function_calls`
+        let analysis = FileAnalysis::parse(
+            r#"{ "imports": [], "classes": [], "functions": [
+                { "name": "f" },
+                { "name": "g", "line": 4, "name_start_col": 4, "name_end_col": 5,
+                  "function_calls": [{ "name": "f" }] }
+            ] }"#,
+        )
+        .unwrap();
+
+        let f = analysis.function("f").unwrap();
+        assert_eq!((f.line, f.name_start_col, f.name_end_col), (1, 0, 0));
+        assert!(f.function_calls.is_empty());
+        assert_eq!(analysis.function("g").unwrap().function_calls[0].line, 1);
+        assert_eq!(
+            analysis.warnings(),
+            vec!["functions[0]: missing name span", "functions[1].function_calls[0]: missing call span"]
+        );
+    }
+}
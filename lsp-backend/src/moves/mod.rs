@@ -0,0 +1,123 @@
+use std::path::{Path, PathBuf};
+use tower_lsp::lsp_types::{Position, Range, TextEdit};
+
+/// Nombre de módulo Python (`paquete.sub.modulo`) de un archivo relativo a la raíz del workspace.
+/// `paquete/__init__.py` corresponde al módulo `paquete`.
+pub fn module_name(root: &Path, path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(root).ok()?;
+    if relative.extension().and_then(|e| e.to_str()) != Some("py") {
+        return None;
+    }
+    let mut parts: Vec<String> = relative
+        .with_extension("")
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().into_owned())
+        .collect();
+    if parts.last().is_some_and(|last| last == "__init__") {
+        parts.pop();
+    }
+    if parts.is_empty() {
+        return None;
+    }
+    Some(parts.join("."))
+}
+
+/// Si `path` está en `old` (o dentro de él, cuando se mueve una carpeta), retorna su nueva ubicación.
+pub fn remap_path(path: &Path, old: &Path, new: &Path) -> Option<PathBuf> {
+    let rest = path.strip_prefix(old).ok()?;
+    if rest.as_os_str().is_empty() {
+        Some(new.to_path_buf())
+    } else {
+        Some(new.join(rest))
+    }
+}
+
+/// Reemplaza el módulo `old_module` (o un submódulo suyo) por `new_module`, si corresponde.
+fn rewrite_module(module: &str, old_module: &str, new_module: &str) -> Option<String> {
+    if module == old_module {
+        return Some(new_module.to_string());
+    }
+    let rest = module.strip_prefix(old_module)?.strip_prefix('.')?;
+    Some(format!("{new_module}.{rest}"))
+}
+
+fn edit(line: usize, start: usize, end: usize, new_text: String) -> TextEdit {
+    TextEdit {
+        range: Range {
+            start: Position { line: line as u32, character: start as u32 },
+            end: Position { line: line as u32, character: end as u32 },
+        },
+        new_text,
+    }
+}
+
+/// Genera los `TextEdit` que reescriben los imports de `old_module` a `new_module` en `text`.
+/// Cubre `import a.b`, `import a.b as c`, `import x, a.b` y `from a.b import f`.
+/// Los imports relativos (`from . import x`) no se tocan.
+pub fn import_rewrite_edits(text: &str, old_module: &str, new_module: &str) -> Vec<TextEdit> {
+    let mut edits = vec![];
+
+    for (line_idx, line) in text.lines().enumerate() {
+        let indent = line.len() - line.trim_start().len();
+        let trimmed = &line[indent..];
+
+        if let Some(rest) = trimmed.strip_prefix("from ") {
+            let module_start = indent + 5 + (rest.len() - rest.trim_start().len());
+            let module: &str = line[module_start..].split_whitespace().next().unwrap_or("");
+            if let Some(new_text) = rewrite_module(module, old_module, new_module) {
+                edits.push(edit(line_idx, module_start, module_start + module.len(), new_text));
+            }
+        } else if trimmed.starts_with("import ") {
+            // Recorremos cada elemento de la lista separada por comas
+            let mut offset = indent + "import ".len();
+            let list_end = line.find('#').unwrap_or(line.len());
+            for item in line[offset..list_end].split(',') {
+                let leading = item.len() - item.trim_start().len();
+                let module = item.split_whitespace().next().unwrap_or("");
+                let start = offset + leading;
+                if let Some(new_text) = rewrite_module(module, old_module, new_module) {
+                    edits.push(edit(line_idx, start, start + module.len(), new_text));
+                }
+                offset += item.len() + 1;
+            }
+        }
+    }
+
+    edits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn module_name_handles_packages_and_nested_modules() {
+        let root = Path::new("/ws");
+        assert_eq!(module_name(root, Path::new("/ws/projecto/shop.py")).as_deref(), Some("projecto.shop"));
+        assert_eq!(module_name(root, Path::new("/ws/projecto/__init__.py")).as_deref(), Some("projecto"));
+        assert_eq!(module_name(root, Path::new("/other/shop.py")), None);
+    }
+
+    #[test]
+    fn import_rewrite_edits_rewrites_from_and_plain_imports() {
+        let text = "import os, projecto.shop as shop\nfrom projecto.shop import add_product\nfrom projecto.shopping import cart\n    from projecto.shop.sub import x\n";
+        let edits = import_rewrite_edits(text, "projecto.shop", "store.shop");
+
+        let rewritten: Vec<(u32, u32, &str)> = edits
+            .iter()
+            .map(|e| (e.range.start.line, e.range.start.character, e.new_text.as_str()))
+            .collect();
+        assert_eq!(
+            rewritten,
+            vec![(0, 11, "store.shop"), (1, 5, "store.shop"), (3, 9, "store.shop.sub")]
+        );
+    }
+
+    #[test]
+    fn remap_path_moves_files_inside_renamed_folders() {
+        let old = Path::new("/ws/projecto");
+        let new = Path::new("/ws/core");
+        assert_eq!(remap_path(Path::new("/ws/projecto/shop.py"), old, new), Some(PathBuf::from("/ws/core/shop.py")));
+        assert_eq!(remap_path(Path::new("/ws/main.py"), old, new), None);
+    }
+}
@@ -0,0 +1,801 @@
+use crate::graph::EdgeKind;
+use crate::model::ClassDef;
+use crate::symbol::SymbolId;
+use crate::{Connections, FunctionsInFiles};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use tower_lsp::lsp_types::{
+    CallHierarchyIncomingCall, CallHierarchyItem, CallHierarchyOutgoingCall, CodeLens, Command,
+    DocumentSymbol, Location, Position, Range, SymbolKind, TextEdit, Url, WorkspaceEdit,
+};
+
+/// Comando del cliente que abre el panel de referencias (convierte los argumentos a tipos de VSCode).
+pub const SHOW_REFERENCES_COMMAND: &str = "lsp-backend.showReferences";
+
+const PYTHON_KEYWORDS: &[&str] = &[
+    "False", "None", "True", "and", "as", "assert", "async", "await", "break", "class",
+    "continue", "def", "del", "elif", "else", "except", "finally", "for", "from", "global",
+    "if", "import", "in", "is", "lambda", "nonlocal", "not", "or", "pass", "raise", "return",
+    "try", "while", "with", "yield",
+];
+
+/// Construye un `Range` LSP sobre una única línea. `line` viene 1-based desde el analizador.
+pub fn line_range(line: i64, start_col: usize, end_col: usize) -> Range {
+    let line = (line.max(1) - 1) as u32;
+    Range {
+        start: Position { line, character: start_col as u32 },
+        end: Position { line, character: end_col as u32 },
+    }
+}
+
+/// Texto por línea de los archivos que aparecen en una respuesta. El analizador informa columnas en
+/// bytes UTF-8 y LSP cuenta `Position.character` en unidades UTF-16: con el texto de la línea se
+/// traduce en ambos sentidos. Las líneas de archivos que no están en el índice quedan igual.
+#[derive(Debug, Default)]
+pub struct LineIndex {
+    files: HashMap<PathBuf, Vec<String>>,
+}
+
+impl LineIndex {
+    pub fn insert(&mut self, path: PathBuf, text: &str) {
+        self.files.insert(path, text.lines().map(str::to_string).collect());
+    }
+
+    pub fn contains(&self, path: &Path) -> bool {
+        self.files.contains_key(path)
+    }
+
+    fn line(&self, path: &Path, line: u32) -> Option<&str> {
+        self.files.get(path)?.get(line as usize).map(String::as_str)
+    }
+
+    /// Columna en bytes → columna UTF-16 (para responder al editor).
+    pub fn encode_position(&self, path: &Path, position: Position) -> Position {
+        let Some(text) = self.line(path, position.line) else {
+            return position;
+        };
+        let mut end = (position.character as usize).min(text.len());
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        Position {
+            line: position.line,
+            character: text[..end].encode_utf16().count() as u32,
+        }
+    }
+
+    pub fn encode_range(&self, path: &Path, range: Range) -> Range {
+        Range {
+            start: self.encode_position(path, range.start),
+            end: self.encode_position(path, range.end),
+        }
+    }
+
+    pub fn encode_location(&self, location: Location) -> Location {
+        let Ok(path) = location.uri.to_file_path() else {
+            return location;
+        };
+        Location {
+            range: self.encode_range(&path, location.range),
+            uri: location.uri,
+        }
+    }
+
+    pub fn encode_item(&self, item: CallHierarchyItem) -> CallHierarchyItem {
+        let Ok(path) = item.uri.to_file_path() else {
+            return item;
+        };
+        CallHierarchyItem {
+            range: self.encode_range(&path, item.range),
+            selection_range: self.encode_range(&path, item.selection_range),
+            ..item
+        }
+    }
+
+    /// Traduce un símbolo del outline de `path` y sus hijos.
+    pub fn encode_symbol(&self, path: &Path, symbol: DocumentSymbol) -> DocumentSymbol {
+        DocumentSymbol {
+            range: self.encode_range(path, symbol.range),
+            selection_range: self.encode_range(path, symbol.selection_range),
+            children: symbol
+                .children
+                .map(|children| children.into_iter().map(|c| self.encode_symbol(path, c)).collect()),
+            ..symbol
+        }
+    }
+
+    /// Columna UTF-16 que manda el editor → columna en bytes (como las informa el analizador).
+    pub fn decode_position(&self, path: &Path, position: Position) -> Position {
+        let Some(text) = self.line(path, position.line) else {
+            return position;
+        };
+        let mut units = 0;
+        let mut character = text.len();
+        for (offset, c) in text.char_indices() {
+            if units >= position.character {
+                character = offset;
+                break;
+            }
+            units += c.len_utf16() as u32;
+        }
+        Position { line: position.line, character: character as u32 }
+    }
+}
+
+/// Retorna `true` si `position` (0-based) cae dentro de `[start_col, end_col]` en la línea `line` (1-based).
+fn contains(line: i64, start_col: usize, end_col: usize, position: Position) -> bool {
+    let character = position.character as usize;
+    line == position.line as i64 + 1 && start_col <= character && character <= end_col
+}
+
+/// Busca la `Connection` cuyo call site en `file` contiene la posición del cursor.
+pub fn connection_at<'a>(
+    connections: &'a [Connections],
+    file: &str,
+    position: Position,
+) -> Option<&'a Connections> {
+    connections_at(connections, file, position).into_iter().next()
+}
+
+/// Todas las `Connections` del call site bajo el cursor: varias si el receptor es una unión
+/// (`Cart | Order`) o si es una instanciación (el `__init__` y la clase).
+pub fn connections_at<'a>(
+    connections: &'a [Connections],
+    file: &str,
+    position: Position,
+) -> Vec<&'a Connections> {
+    connections
+        .iter()
+        .filter(|c| c.file_use == file && contains(c.line, c.start_col, c.end_col, position))
+        .collect()
+}
+
+/// `true` si la posición está sobre una instanciación (`Order(...)`): no se puede renombrar desde ahí.
+pub fn is_instantiation_at(connections: &[Connections], file: &str, position: Position) -> bool {
+    connection_at(connections, file, position).is_some_and(|c| c.kind == EdgeKind::Instantiates)
+}
+
+/// Retorna la definición (`FunctionsInFiles`) a la que resuelve una `Connection`.
+pub fn definition_for<'a>(
+    functions_in_file: &'a [FunctionsInFiles],
+    connection: &Connections,
+) -> Option<&'a FunctionsInFiles> {
+    functions_in_file
+        .iter()
+        .find(|f| f.symbol == connection.target)
+}
+
+/// Busca la definición cuyo nombre contiene la posición del cursor en `file`.
+pub fn definition_at<'a>(
+    functions_in_file: &'a [FunctionsInFiles],
+    file: &str,
+    position: Position,
+) -> Option<&'a FunctionsInFiles> {
+    functions_in_file.iter().find(|f| {
+        f.symbol.module == file && contains(f.line, f.name_start_col, f.name_end_col, position)
+    })
+}
+
+/// Resuelve la función bajo el cursor, ya sea parado sobre su definición o sobre un call site.
+pub fn symbol_at<'a>(
+    connections: &[Connections],
+    functions_in_file: &'a [FunctionsInFiles],
+    file: &str,
+    position: Position,
+) -> Option<&'a FunctionsInFiles> {
+    definition_at(functions_in_file, file, position).or_else(|| {
+        let connection = connection_at(connections, file, position)?;
+        definition_for(functions_in_file, connection)
+    })
+}
+
+/// Retorna todos los call sites resueltos hacia `definition` en el workspace.
+pub fn references_to<'a>(
+    connections: &'a [Connections],
+    definition: &FunctionsInFiles,
+) -> Vec<&'a Connections> {
+    connections
+        .iter()
+        .filter(|c| c.target == definition.symbol)
+        .collect()
+}
+
+/// Retorna todas las llamadas resueltas que se hacen desde el cuerpo de `definition`.
+pub fn calls_from<'a>(
+    connections: &'a [Connections],
+    definition: &FunctionsInFiles,
+) -> Vec<&'a Connections> {
+    connections
+        .iter()
+        .filter(|c| c.caller == definition.symbol)
+        .collect()
+}
+
+/// Convierte el call site de una `Connection` en una `Location` LSP.
+pub fn call_site_location(connection: &Connections) -> Option<Location> {
+    let uri = Url::from_file_path(&connection.file_use).ok()?;
+    Some(Location {
+        uri,
+        range: line_range(connection.line, connection.start_col, connection.end_col),
+    })
+}
+
+/// Convierte una definición en una `Location` que apunta al nombre de la función.
+pub fn definition_location(definition: &FunctionsInFiles) -> Option<Location> {
+    let uri = Url::from_file_path(&definition.symbol.module).ok()?;
+    Some(Location {
+        uri,
+        range: line_range(definition.line, definition.name_start_col, definition.name_end_col),
+    })
+}
+
+/// Convierte la definición de una clase en una `Location` que apunta a su nombre. `None` si el
+/// analizador no informó su posición.
+pub fn class_location(class: &SymbolId, definition: &ClassDef) -> Option<Location> {
+    let uri = Url::from_file_path(&class.module).ok()?;
+    let line = definition.line?;
+    Some(Location {
+        uri,
+        range: line_range(line, definition.name_start_col, definition.name_end_col.max(definition.name_start_col)),
+    })
+}
+
+/// Retorna el rango del token bajo el cursor (nombre en la definición o en el call site).
+pub fn symbol_range_at(
+    connections: &[Connections],
+    functions_in_file: &[FunctionsInFiles],
+    file: &str,
+    position: Position,
+) -> Option<Range> {
+    if let Some(def) = definition_at(functions_in_file, file, position) {
+        return Some(line_range(def.line, def.name_start_col, def.name_end_col));
+    }
+    connection_at(connections, file, position).map(|c| line_range(c.line, c.start_col, c.end_col))
+}
+
+/// Retorna `true` si `name` es un identificador Python válido que no es palabra reservada.
+pub fn is_valid_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    let Some(first) = chars.next() else {
+        return false;
+    };
+    (first == '_' || first.is_alphabetic())
+        && chars.all(|c| c == '_' || c.is_alphanumeric())
+        && !PYTHON_KEYWORDS.contains(&name)
+}
+
+/// `true` si el call site de `call` también resuelve a otro símbolo (un receptor `Cart | Order`
+/// genera una connection por alternativa).
+fn has_other_targets(connections: &[Connections], call: &Connections) -> bool {
+    connections.iter().any(|c| {
+        c.file_use == call.file_use
+            && c.line == call.line
+            && c.start_col == call.start_col
+            && c.end_col == call.end_col
+            && c.target != call.target
+    })
+}
+
+/// Call sites de `definition` que cambian al renombrarla. Las instanciaciones (`Order(...)` → `Order.__init__`)
+/// quedan afuera: en el código aparece el nombre de la clase, no el del método.
+pub fn renamed_call_sites<'a>(
+    connections: &'a [Connections],
+    definition: &FunctionsInFiles,
+) -> Vec<&'a Connections> {
+    references_to(connections, definition)
+        .into_iter()
+        .filter(|c| c.kind != EdgeKind::Instantiates)
+        .collect()
+}
+
+/// Call sites de `definition` con más de un destino posible (receptor `Cart | Order`): reescribirlos
+/// rompería la llamada para las otras alternativas, así que bloquean el rename.
+pub fn ambiguous_call_sites<'a>(
+    connections: &'a [Connections],
+    definition: &FunctionsInFiles,
+) -> Vec<&'a Connections> {
+    renamed_call_sites(connections, definition)
+        .into_iter()
+        .filter(|c| has_other_targets(connections, c))
+        .collect()
+}
+
+/// Rangos (línea 0-based, columnas en bytes) donde `name` aparece importado desde el módulo `module`
+/// en `text`: `from m import f`, `from m import a, f as g` y la forma con paréntesis en varias líneas.
+/// Para los imports relativos (`from .m import f`) alcanza con que coincida el último componente.
+pub fn imported_name_ranges(text: &str, module: &str, name: &str) -> Vec<Range> {
+    let module_tail = module.rsplit('.').next().unwrap_or(module);
+    let lines: Vec<&str> = text.lines().collect();
+    let mut ranges = vec![];
+
+    let mut idx = 0;
+    while idx < lines.len() {
+        let line = lines[idx];
+        idx += 1;
+        let trimmed = line.trim_start();
+        let Some(rest) = trimmed.strip_prefix("from ") else {
+            continue;
+        };
+        let Some((from_module, _)) = rest.trim_start().split_once(char::is_whitespace) else {
+            continue;
+        };
+        let Some(import_at) = line.find(" import ") else {
+            continue;
+        };
+        let matches = from_module == module
+            || (from_module.starts_with('.')
+                && from_module.trim_start_matches('.').rsplit('.').next() == Some(module_tail));
+
+        // Segmentos (línea, offset, texto) que contienen la lista de nombres importados
+        let start = import_at + " import ".len();
+        let mut segments = vec![(idx - 1, start, &line[start..])];
+        if line[start..].trim_start().starts_with('(') && !line[start..].contains(')') {
+            while idx < lines.len() {
+                segments.push((idx, 0, lines[idx]));
+                idx += 1;
+                if lines[idx - 1].contains(')') {
+                    break;
+                }
+            }
+        }
+        if !matches {
+            continue;
+        }
+
+        for (line_idx, offset, segment) in segments {
+            let segment = segment.split('#').next().unwrap_or("");
+            let mut item_offset = offset;
+            for item in segment.split(',') {
+                let cleaned = item.trim_start_matches(|c: char| c.is_whitespace() || c == '(');
+                let leading = item.len() - cleaned.len();
+                let imported = cleaned
+                    .split(|c: char| c.is_whitespace() || c == ')')
+                    .next()
+                    .unwrap_or("");
+                if imported == name {
+                    let start_col = item_offset + leading;
+                    ranges.push(Range {
+                        start: Position { line: line_idx as u32, character: start_col as u32 },
+                        end: Position { line: line_idx as u32, character: (start_col + name.len()) as u32 },
+                    });
+                }
+                item_offset += item.len() + 1;
+            }
+        }
+    }
+
+    ranges
+}
+
+/// Construye el `WorkspaceEdit` que renombra `definition` en su definición, en todos sus call sites
+/// y en los `from m import f` de `import_sites`.
+pub fn rename_edit(
+    connections: &[Connections],
+    definition: &FunctionsInFiles,
+    import_sites: &[Location],
+    new_name: &str,
+) -> WorkspaceEdit {
+    let locations = definition_location(definition)
+        .into_iter()
+        .chain(
+            renamed_call_sites(connections, definition)
+                .into_iter()
+                .filter_map(call_site_location),
+        )
+        .chain(import_sites.iter().cloned());
+
+    let mut changes: HashMap<Url, Vec<TextEdit>> = HashMap::new();
+    for location in locations {
+        let edits = changes.entry(location.uri).or_default();
+        // Una misma llamada puede quedar registrada dos veces (p. ej. método y función homónimos)
+        if edits.iter().any(|e| e.range == location.range) {
+            continue;
+        }
+        edits.push(TextEdit {
+            range: location.range,
+            new_text: new_name.to_string(),
+        });
+    }
+
+    WorkspaceEdit {
+        changes: Some(changes),
+        ..Default::default()
+    }
+}
+
+/// Convierte una definición en un `CallHierarchyItem` (función top-level o método de clase).
+pub fn call_hierarchy_item(definition: &FunctionsInFiles) -> Option<CallHierarchyItem> {
+    let uri = Url::from_file_path(&definition.symbol.module).ok()?;
+    let range = line_range(definition.line, definition.name_start_col, definition.name_end_col);
+    let kind = if definition.symbol.class_name.is_some() {
+        SymbolKind::METHOD
+    } else {
+        SymbolKind::FUNCTION
+    };
+    Some(CallHierarchyItem {
+        name: definition.symbol.name.clone(),
+        kind,
+        tags: None,
+        detail: definition.symbol.class_name.clone(),
+        uri,
+        range,
+        selection_range: range,
+        data: None,
+    })
+}
+
+/// Busca la definición que corresponde a un `CallHierarchyItem` devuelto previamente.
+pub fn definition_for_item<'a>(
+    functions_in_file: &'a [FunctionsInFiles],
+    item: &CallHierarchyItem,
+) -> Option<&'a FunctionsInFiles> {
+    let path = item.uri.to_file_path().ok()?;
+    definition_at(functions_in_file, &path.to_string_lossy(), item.selection_range.start)
+}
+
+/// Agrupa los call sites hacia `definition` por función llamadora.
+pub fn incoming_calls(
+    connections: &[Connections],
+    functions_in_file: &[FunctionsInFiles],
+    definition: &FunctionsInFiles,
+) -> Vec<CallHierarchyIncomingCall> {
+    let mut by_caller: BTreeMap<&SymbolId, Vec<Range>> = BTreeMap::new();
+    for c in references_to(connections, definition) {
+        by_caller
+            .entry(&c.caller)
+            .or_default()
+            .push(line_range(c.line, c.start_col, c.end_col));
+    }
+
+    by_caller
+        .into_iter()
+        .filter_map(|(caller, from_ranges)| {
+            let caller_def = functions_in_file.iter().find(|f| f.symbol == *caller)?;
+            Some(CallHierarchyIncomingCall {
+                from: call_hierarchy_item(caller_def)?,
+                from_ranges,
+            })
+        })
+        .collect()
+}
+
+/// Agrupa las llamadas hechas desde `definition` por función destino.
+pub fn outgoing_calls(
+    connections: &[Connections],
+    functions_in_file: &[FunctionsInFiles],
+    definition: &FunctionsInFiles,
+) -> Vec<CallHierarchyOutgoingCall> {
+    let mut by_callee: BTreeMap<&SymbolId, Vec<Range>> = BTreeMap::new();
+    for c in calls_from(connections, definition) {
+        by_callee
+            .entry(&c.target)
+            .or_default()
+            .push(line_range(c.line, c.start_col, c.end_col));
+    }
+
+    by_callee
+        .into_iter()
+        .filter_map(|(callee, from_ranges)| {
+            let callee_def = functions_in_file.iter().find(|f| f.symbol == *callee)?;
+            Some(CallHierarchyOutgoingCall {
+                to: call_hierarchy_item(callee_def)?,
+                from_ranges,
+            })
+        })
+        .collect()
+}
+
+/// Arma los code lens de un archivo: "N references · M files" sobre cada definición
+/// (clic → abre las referencias) y un lens "unused" para las funciones sin uso.
+pub fn code_lenses(
+    connections: &[Connections],
+    functions_in_file: &[FunctionsInFiles],
+    unused: &[FunctionsInFiles],
+    file: &str,
+    lines: &LineIndex,
+) -> Vec<CodeLens> {
+    functions_in_file
+        .iter()
+        .filter(|f| f.symbol.module == file)
+        .filter_map(|definition| {
+            let location = lines.encode_location(definition_location(definition)?);
+            // Las sin uso muestran "unused" con el mismo comando y una lista vacía, así el clic
+            // abre el panel de referencias vacío en vez de un comando inexistente.
+            let is_unused = unused.iter().any(|u| u.symbol == definition.symbol);
+            let (title, locations) = if is_unused {
+                ("unused".to_string(), vec![])
+            } else {
+                let references = references_to(connections, definition);
+                let files: HashSet<&str> =
+                    references.iter().map(|c| c.file_use.as_str()).collect();
+                let locations: Vec<Location> = references
+                    .into_iter()
+                    .filter_map(call_site_location)
+                    .map(|location| lines.encode_location(location))
+                    .collect();
+                let title = format!(
+                    "{} reference{} · {} file{}",
+                    locations.len(),
+                    if locations.len() == 1 { "" } else { "s" },
+                    files.len(),
+                    if files.len() == 1 { "" } else { "s" },
+                );
+                (title, locations)
+            };
+
+            Some(CodeLens {
+                range: location.range,
+                command: Some(Command {
+                    title,
+                    command: SHOW_REFERENCES_COMMAND.to_string(),
+                    arguments: Some(vec![
+                        serde_json::json!(location.uri),
+                        serde_json::json!(location.range.start),
+                        serde_json::json!(locations),
+                    ]),
+                }),
+                data: None,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn connection(file_use: &str, line: i64, start_col: usize, end_col: usize) -> Connections {
+        Connections {
+            file_use: file_use.to_string(),
+            line,
+            start_col,
+            end_col,
+            target: SymbolId::function("/ws/shop.py", "add_product"),
+            caller: SymbolId::function(file_use, "main"),
+            dangling: false,
+            kind: EdgeKind::Calls,
+        }
+    }
+
+    fn definition() -> FunctionsInFiles {
+        FunctionsInFiles {
+            symbol: SymbolId::function("/ws/shop.py", "add_product"),
+            line: 7,
+            name_start_col: 4,
+            name_end_col: 15,
+        }
+    }
+
+    #[test]
+    fn connection_at_matches_cursor_inside_call_site() {
+        let connections = vec![connection("/ws/main.py", 3, 4, 15)];
+
+        let hit = connection_at(&connections, "/ws/main.py", Position { line: 2, character: 10 });
+        assert!(hit.is_some());
+
+        let other_line = connection_at(&connections, "/ws/main.py", Position { line: 3, character: 10 });
+        assert!(other_line.is_none());
+
+        let other_file = connection_at(&connections, "/ws/shop.py", Position { line: 2, character: 10 });
+        assert!(other_file.is_none());
+    }
+
+    #[test]
+    fn symbol_at_resolves_from_definition_and_call_site() {
+        let functions = vec![definition()];
+        let connections = vec![
+            connection("/ws/main.py", 3, 4, 15),
+            connection("/ws/orders.py", 12, 8, 19),
+        ];
+
+        let from_def = symbol_at(&connections, &functions, "/ws/shop.py", Position { line: 6, character: 6 });
+        let from_call = symbol_at(&connections, &functions, "/ws/main.py", Position { line: 2, character: 6 });
+        assert_eq!(from_def.map(|f| f.line), Some(7));
+        assert_eq!(from_call.map(|f| f.line), Some(7));
+
+        assert_eq!(references_to(&connections, &functions[0]).len(), 2);
+    }
+
+    #[test]
+    fn rename_edit_covers_definition_and_call_sites_once() {
+        let def = definition();
+        let connections = vec![
+            connection("/ws/main.py", 3, 4, 15),
+            connection("/ws/main.py", 3, 4, 15),
+            connection("/ws/main.py", 9, 0, 11),
+        ];
+
+        let edit = rename_edit(&connections, &def, &[], "create_product");
+        let changes = edit.changes.unwrap();
+        let main_uri = Url::from_file_path("/ws/main.py").unwrap();
+        let shop_uri = Url::from_file_path("/ws/shop.py").unwrap();
+        assert_eq!(changes[&main_uri].len(), 2);
+        assert_eq!(changes[&shop_uri].len(), 1);
+        assert!(changes[&shop_uri].iter().all(|e| e.new_text == "create_product"));
+    }
+
+    #[test]
+    fn renaming_init_skips_instantiation_call_sites() {
+        let init = FunctionsInFiles {
+            symbol: SymbolId::method("/ws/shop.py", "Order", "__init__"),
+            line: 2,
+            name_start_col: 8,
+            name_end_col: 16,
+        };
+        // `super().__init__()` sí nombra al método; `Order(...)` nombra a la clase
+        let mut explicit = connection("/ws/shop.py", 12, 16, 24);
+        explicit.target = init.symbol.clone();
+        let mut instantiation = connection("/ws/main.py", 3, 8, 13);
+        instantiation.target = init.symbol.clone();
+        instantiation.kind = EdgeKind::Instantiates;
+        let connections = vec![explicit, instantiation];
+
+        assert_eq!(references_to(&connections, &init).len(), 2);
+        assert_eq!(renamed_call_sites(&connections, &init).len(), 1);
+        assert!(is_instantiation_at(&connections, "/ws/main.py", Position { line: 2, character: 10 }));
+        assert!(!is_instantiation_at(&connections, "/ws/shop.py", Position { line: 11, character: 20 }));
+    }
+
+    #[test]
+    fn connections_at_returns_every_target_of_a_call_site() {
+        let mut cart = connection("/ws/main.py", 4, 11, 16);
+        cart.target = SymbolId::method("/ws/shop.py", "Cart", "total");
+        let mut order = connection("/ws/main.py", 4, 11, 16);
+        order.target = SymbolId::method("/ws/shop.py", "Order", "total");
+        let other = connection("/ws/main.py", 6, 4, 9);
+        let connections = vec![cart, order, other];
+
+        let position = Position { line: 3, character: 12 };
+        let targets: Vec<&str> = connections_at(&connections, "/ws/main.py", position)
+            .iter()
+            .map(|c| c.target.class_name.as_deref().unwrap_or_default())
+            .collect();
+        assert_eq!(targets, vec!["Cart", "Order"]);
+        assert_eq!(connection_at(&connections, "/ws/main.py", position).unwrap().target.class_name.as_deref(), Some("Cart"));
+    }
+
+    #[test]
+    fn renaming_reports_call_sites_with_several_candidate_targets() {
+        let cart_total = FunctionsInFiles {
+            symbol: SymbolId::method("/ws/shop.py", "Cart", "total"),
+            line: 3,
+            name_start_col: 8,
+            name_end_col: 13,
+        };
+        // `basket.total()` con `basket: Cart | Order`: una connection por alternativa
+        let mut union_cart = connection("/ws/main.py", 4, 11, 16);
+        union_cart.target = cart_total.symbol.clone();
+        let mut union_order = connection("/ws/main.py", 4, 11, 16);
+        union_order.target = SymbolId::method("/ws/shop.py", "Order", "total");
+        // `cart.total()` con `cart: Cart`
+        let mut plain = connection("/ws/main.py", 6, 9, 14);
+        plain.target = cart_total.symbol.clone();
+        let connections = vec![union_cart, union_order, plain];
+
+        assert_eq!(references_to(&connections, &cart_total).len(), 2);
+        let ambiguous = ambiguous_call_sites(&connections, &cart_total);
+        assert_eq!(ambiguous.len(), 1);
+        assert_eq!(ambiguous[0].line, 4);
+    }
+
+    #[test]
+    fn imported_name_ranges_cover_single_line_and_parenthesized_imports() {
+        let text = "from projecto.shop import add_product, total as t\nfrom .shop import (\n    cart,\n    add_product,\n)\nfrom other import add_product\nimport projecto.shop\n";
+        let ranges: Vec<(u32, u32, u32)> = imported_name_ranges(text, "projecto.shop", "add_product")
+            .iter()
+            .map(|r| (r.start.line, r.start.character, r.end.character))
+            .collect();
+        assert_eq!(ranges, vec![(0, 26, 37), (3, 4, 15)]);
+    }
+
+    #[test]
+    fn line_index_translates_byte_columns_to_utf16() {
+        let mut lines = LineIndex::default();
+        let path = PathBuf::from("/ws/main.py");
+        // `ñ` ocupa 2 bytes y 1 unidad UTF-16; `🛒` ocupa 4 bytes y 2 unidades
+        lines.insert(path.clone(), "año = 1\nx = \"🛒\"; add_product()\n");
+
+        let encoded = lines.encode_position(&path, Position { line: 1, character: 12 });
+        assert_eq!(encoded, Position { line: 1, character: 10 });
+        assert_eq!(lines.decode_position(&path, encoded), Position { line: 1, character: 12 });
+        assert_eq!(lines.encode_position(&path, Position { line: 0, character: 4 }).character, 3);
+        // Archivos fuera del índice quedan igual
+        let other = Position { line: 0, character: 7 };
+        assert_eq!(lines.encode_position(Path::new("/ws/other.py"), other), other);
+    }
+
+    #[test]
+    fn references_distinguish_homonymous_methods_of_different_classes() {
+        let cart_save = FunctionsInFiles {
+            symbol: SymbolId::method("/ws/shop.py", "Cart", "save"),
+            line: 3,
+            name_start_col: 8,
+            name_end_col: 12,
+        };
+        let order_save = FunctionsInFiles {
+            symbol: SymbolId::method("/ws/shop.py", "Order", "save"),
+            line: 9,
+            name_start_col: 8,
+            name_end_col: 12,
+        };
+        let mut call = connection("/ws/main.py", 3, 6, 10);
+        call.target = order_save.symbol.clone();
+        let connections = vec![call];
+
+        assert!(references_to(&connections, &cart_save).is_empty());
+        assert_eq!(references_to(&connections, &order_save).len(), 1);
+        let edit = rename_edit(&connections, &cart_save, &[], "store");
+        let main_uri = Url::from_file_path("/ws/main.py").unwrap();
+        assert!(!edit.changes.unwrap().contains_key(&main_uri));
+    }
+
+    #[test]
+    fn is_valid_identifier_rejects_keywords_and_bad_chars() {
+        assert!(is_valid_identifier("create_product"));
+        assert!(is_valid_identifier("_private2"));
+        assert!(!is_valid_identifier("2fast"));
+        assert!(!is_valid_identifier("class"));
+        assert!(!is_valid_identifier("with-dash"));
+        assert!(!is_valid_identifier(""));
+    }
+
+    #[test]
+    fn call_hierarchy_groups_calls_by_caller_and_callee() {
+        let main_def = FunctionsInFiles {
+            symbol: SymbolId::function("/ws/main.py", "main"),
+            line: 2,
+            name_start_col: 4,
+            name_end_col: 8,
+        };
+        let functions = vec![definition(), main_def.clone()];
+        let connections = vec![
+            connection("/ws/main.py", 3, 4, 15),
+            connection("/ws/main.py", 5, 4, 15),
+        ];
+
+        let incoming = incoming_calls(&connections, &functions, &functions[0]);
+        assert_eq!(incoming.len(), 1);
+        assert_eq!(incoming[0].from.name, "main");
+        assert_eq!(incoming[0].from_ranges.len(), 2);
+
+        let outgoing = outgoing_calls(&connections, &functions, &main_def);
+        assert_eq!(outgoing.len(), 1);
+        assert_eq!(outgoing[0].to.name, "add_product");
+        assert_eq!(outgoing[0].to.kind, SymbolKind::FUNCTION);
+    }
+
+    #[test]
+    fn code_lenses_count_references_and_flag_unused() {
+        let mut unused_def = definition();
+        unused_def.symbol.name = "remove_product".to_string();
+        unused_def.line = 20;
+        let functions = vec![definition(), unused_def.clone()];
+        let connections = vec![
+            connection("/ws/main.py", 3, 4, 15),
+            connection("/ws/main.py", 5, 4, 15),
+            connection("/ws/orders.py", 12, 8, 19),
+        ];
+
+        let lenses = code_lenses(&connections, &functions, &[unused_def], "/ws/shop.py", &LineIndex::default());
+        let titles: Vec<&str> = lenses
+            .iter()
+            .map(|l| l.command.as_ref().unwrap().title.as_str())
+            .collect();
+        assert_eq!(titles, vec!["3 references · 2 files", "unused"]);
+
+        let unused_command = lenses[1].command.as_ref().unwrap();
+        assert_eq!(unused_command.command, SHOW_REFERENCES_COMMAND);
+        assert_eq!(unused_command.arguments.as_ref().unwrap()[2], serde_json::json!([]));
+    }
+
+    #[test]
+    fn definition_location_points_to_function_name() {
+        let functions = vec![definition()];
+        let call = connection("/ws/main.py", 3, 4, 15);
+
+        let def = definition_for(&functions, &call).expect("debe encontrar la definición");
+        let location = definition_location(def).unwrap();
+        assert_eq!(location.range.start, Position { line: 6, character: 4 });
+        assert_eq!(location.range.end, Position { line: 6, character: 15 });
+    }
+}
@@ -0,0 +1,111 @@
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use tower_lsp::Client;
+use tower_lsp::lsp_types::notification::Progress;
+use tower_lsp::lsp_types::request::WorkDoneProgressCreate;
+use tower_lsp::lsp_types::{
+    NumberOrString, ProgressParams, ProgressParamsValue, ProgressToken, WorkDoneProgress,
+    WorkDoneProgressBegin, WorkDoneProgressCreateParams, WorkDoneProgressEnd,
+    WorkDoneProgressReport,
+};
+
+// Contador global para generar tokens de progreso únicos
+static NEXT_TOKEN: AtomicU64 = AtomicU64::new(1);
+
+/// Mensaje de avance que ve el usuario, por ejemplo "12 of 300 files".
+pub fn files_message(done: usize, total: usize) -> String {
+    format!("{done} of {total} files")
+}
+
+/// Porcentaje entero de avance (100 cuando no hay nada que procesar).
+pub fn percentage(done: usize, total: usize) -> u32 {
+    if total == 0 {
+        return 100;
+    }
+    ((done.min(total) * 100) / total) as u32
+}
+
+/// Progreso work-done (`$/progress`) de un análisis largo, cancelable desde el cliente.
+/// Sólo notifica cuando cambia el porcentaje, para no inundar al cliente en workspaces grandes.
+pub struct WorkProgress {
+    client: Client,
+    token: ProgressToken,
+    total: usize,
+    last_percentage: AtomicU32,
+}
+
+impl WorkProgress {
+    /// Crea el token en el cliente y envía el `Begin`. Si el cliente no soporta progreso
+    /// (rechaza el `window/workDoneProgress/create`), las notificaciones siguientes se ignoran del otro lado.
+    pub async fn begin(client: &Client, title: &str, total: usize) -> Self {
+        let token = NumberOrString::String(format!(
+            "lsp-backend/analysis/{}",
+            NEXT_TOKEN.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = client
+            .send_request::<WorkDoneProgressCreate>(WorkDoneProgressCreateParams {
+                token: token.clone(),
+            })
+            .await;
+
+        let progress = WorkProgress {
+            client: client.clone(),
+            token,
+            total,
+            last_percentage: AtomicU32::new(0),
+        };
+        progress
+            .send(WorkDoneProgress::Begin(WorkDoneProgressBegin {
+                title: title.to_string(),
+                cancellable: Some(true),
+                message: Some(files_message(0, total)),
+                percentage: Some(0),
+            }))
+            .await;
+        progress
+    }
+
+    /// Reporta `done` de `total` archivos procesados.
+    pub async fn report(&self, done: usize) {
+        let pct = percentage(done, self.total);
+        if self.last_percentage.swap(pct, Ordering::Relaxed) == pct && done < self.total {
+            return;
+        }
+        self.send(WorkDoneProgress::Report(WorkDoneProgressReport {
+            cancellable: Some(true),
+            message: Some(files_message(done, self.total)),
+            percentage: Some(pct),
+        }))
+        .await;
+    }
+
+    /// Cierra el progreso con un mensaje final.
+    pub async fn end(self, message: String) {
+        self.send(WorkDoneProgress::End(WorkDoneProgressEnd {
+            message: Some(message),
+        }))
+        .await;
+    }
+
+    async fn send(&self, value: WorkDoneProgress) {
+        self.client
+            .send_notification::<Progress>(ProgressParams {
+                token: self.token.clone(),
+                value: ProgressParamsValue::WorkDone(value),
+            })
+            .await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percentage_and_message_track_files_done() {
+        assert_eq!(files_message(12, 300), "12 of 300 files");
+        assert_eq!(percentage(0, 300), 0);
+        assert_eq!(percentage(150, 300), 50);
+        assert_eq!(percentage(300, 300), 100);
+        assert_eq!(percentage(0, 0), 100);
+    }
+}
@@ -0,0 +1,96 @@
+use crate::moves;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Archivo cuyo último análisis falló o devolvió una salida inválida.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct QuarantinedFile {
+    pub path: String,
+    pub reason: String,
+    // Momento (RFC 3339) del primer fallo desde el último análisis válido
+    pub since: String,
+}
+
+/// Archivos en cuarentena. Un archivo en cuarentena conserva en el store su último análisis
+/// válido (si lo tuvo) y sale de la cuarentena en cuanto vuelve a analizarse sin errores.
+#[derive(Debug, Default)]
+pub struct Quarantine {
+    files: BTreeMap<PathBuf, QuarantinedFile>,
+}
+
+impl Quarantine {
+    /// Pone `path` en cuarentena (o actualiza el motivo si ya estaba). Retorna `true` si es nuevo.
+    pub fn isolate(&mut self, path: &Path, reason: String) -> bool {
+        match self.files.get_mut(path) {
+            Some(entry) => {
+                entry.reason = reason;
+                false
+            }
+            None => {
+                self.files.insert(
+                    path.to_path_buf(),
+                    QuarantinedFile {
+                        path: path.to_string_lossy().into_owned(),
+                        reason,
+                        since: chrono::Utc::now().to_rfc3339(),
+                    },
+                );
+                true
+            }
+        }
+    }
+
+    /// Saca a `path` de la cuarentena. Retorna `true` si estaba.
+    pub fn release(&mut self, path: &Path) -> bool {
+        self.files.remove(path).is_some()
+    }
+
+    /// Mueve las entradas de `old` (archivo o carpeta) a su nuevo path.
+    pub fn migrate(&mut self, old: &Path, new: &Path) {
+        let moved: Vec<(PathBuf, PathBuf)> = self
+            .files
+            .keys()
+            .filter_map(|p| Some((p.clone(), moves::remap_path(p, old, new)?)))
+            .collect();
+        for (from, to) in moved {
+            if let Some(mut entry) = self.files.remove(&from) {
+                entry.path = to.to_string_lossy().into_owned();
+                self.files.insert(to, entry);
+            }
+        }
+    }
+
+    /// Archivos en cuarentena, en orden de path.
+    pub fn files(&self) -> impl Iterator<Item = &QuarantinedFile> {
+        self.files.values()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn isolate_keeps_first_failure_time_and_release_clears_it() {
+        let mut quarantine = Quarantine::default();
+        let path = Path::new("/ws/shop.py");
+
+        assert!(quarantine.isolate(path, "malformed analysis".to_string()));
+        let since = quarantine.files().next().unwrap().since.clone();
+        assert!(!quarantine.isolate(path, "analyzer crashed".to_string()));
+        let entry = quarantine.files().next().unwrap();
+        assert_eq!((entry.reason.as_str(), &entry.since), ("analyzer crashed", &since));
+
+        quarantine.migrate(Path::new("/ws"), Path::new("/ws2"));
+        assert_eq!(quarantine.files().next().unwrap().path, "/ws2/shop.py");
+
+        assert!(quarantine.release(Path::new("/ws2/shop.py")));
+        assert!(!quarantine.release(Path::new("/ws2/shop.py")));
+        assert!(quarantine.is_empty());
+    }
+}
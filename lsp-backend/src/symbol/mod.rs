@@ -0,0 +1,125 @@
+use crate::graph::relativize;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+
+/// Tipo de símbolo que identifica un `SymbolId`.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum SymbolKind {
+    Function,
+    Method,
+    Class,
+}
+
+/// Identificador canónico de un símbolo del workspace: módulo (path absoluto del archivo),
+/// clase contenedora, nombre y tipo. Dos métodos homónimos de clases distintas no colisionan.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SymbolId {
+    pub module: String,
+    pub class_name: Option<String>,
+    pub name: String,
+    pub kind: SymbolKind,
+}
+
+impl SymbolId {
+    pub fn function(module: &str, name: &str) -> SymbolId {
+        SymbolId {
+            module: module.to_string(),
+            class_name: None,
+            name: name.to_string(),
+            kind: SymbolKind::Function,
+        }
+    }
+
+    pub fn method(module: &str, class_name: &str, name: &str) -> SymbolId {
+        SymbolId {
+            module: module.to_string(),
+            class_name: Some(class_name.to_string()),
+            name: name.to_string(),
+            kind: SymbolKind::Method,
+        }
+    }
+
+    pub fn class(module: &str, name: &str) -> SymbolId {
+        SymbolId {
+            module: module.to_string(),
+            class_name: None,
+            name: name.to_string(),
+            kind: SymbolKind::Class,
+        }
+    }
+
+    /// ID del nodo en el grafo del frontend (`fn::`, `mth::` o `cls::`), con el módulo relativo al workspace.
+    pub fn node_id(&self, root: &Path) -> String {
+        let module = relativize(&self.module, root);
+        match (self.kind, &self.class_name) {
+            (SymbolKind::Method, Some(class_name)) => {
+                format!("mth::{module}::{class_name}::{}", self.name)
+            }
+            (SymbolKind::Class, _) => format!("cls::{module}::{}", self.name),
+            _ => format!("fn::{module}::{}", self.name),
+        }
+    }
+
+    /// Inverso de `node_id`: reconstruye el símbolo a partir del ID que usa el frontend.
+    pub fn from_node_id(id: &str, root: &Path) -> Option<SymbolId> {
+        let (prefix, rest) = id.split_once("::")?;
+        let (module, name) = rest.rsplit_once("::")?;
+        match prefix {
+            "fn" => Some(SymbolId::function(&root.join(module).to_string_lossy(), name)),
+            "cls" => Some(SymbolId::class(&root.join(module).to_string_lossy(), name)),
+            "mth" => {
+                let (module, class_name) = module.rsplit_once("::")?;
+                Some(SymbolId::method(&root.join(module).to_string_lossy(), class_name, name))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Nombre para mostrar al usuario: `Clase.metodo` o `funcion`.
+impl fmt::Display for SymbolId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.class_name {
+            Some(class_name) => write!(f, "{class_name}.{}", self.name),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn node_ids_round_trip_for_every_kind() {
+        let root = Path::new("/ws");
+        let symbols = [
+            SymbolId::function("/ws/pkg/shop.py", "add_product"),
+            SymbolId::method("/ws/pkg/shop.py", "Cart", "add"),
+            SymbolId::class("/ws/pkg/shop.py", "Cart"),
+        ];
+        let ids: Vec<String> = symbols.iter().map(|s| s.node_id(root)).collect();
+        assert_eq!(
+            ids,
+            vec![
+                "fn::pkg/shop.py::add_product",
+                "mth::pkg/shop.py::Cart::add",
+                "cls::pkg/shop.py::Cart",
+            ]
+        );
+        for (symbol, id) in symbols.iter().zip(&ids) {
+            assert_eq!(SymbolId::from_node_id(id, root).as_ref(), Some(symbol));
+        }
+    }
+
+    #[test]
+    fn methods_with_the_same_name_in_different_classes_are_distinct() {
+        let cart = SymbolId::method("/ws/shop.py", "Cart", "save");
+        let order = SymbolId::method("/ws/shop.py", "Order", "save");
+        assert_ne!(cart, order);
+        assert_eq!(cart.to_string(), "Cart.save");
+        assert_eq!(SymbolId::function("/ws/shop.py", "main").to_string(), "main");
+    }
+}
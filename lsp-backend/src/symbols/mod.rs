@@ -0,0 +1,195 @@
+use crate::FunctionsInFiles;
+use crate::navigation::line_range;
+use crate::model::{ClassDef, FileAnalysis, FunctionDef};
+use tower_lsp::lsp_types::{
+    DocumentSymbol, Location, Position, Range, SymbolInformation, SymbolKind, Url,
+};
+
+const WORKSPACE_SYMBOL_LIMIT: usize = 200;
+
+/// Rango completo de una definición: desde el inicio de su línea hasta `end_line` (si el analizador
+/// lo provee) o, en su defecto, hasta el final del nombre.
+fn full_range(end_line: Option<i64>, selection: Range) -> Range {
+    let end = match end_line {
+        Some(end_line) if end_line >= 1 && (end_line - 1) as u32 > selection.end.line => Position {
+            line: (end_line - 1) as u32,
+            character: u32::MAX,
+        },
+        _ => selection.end,
+    };
+    Range {
+        start: Position { line: selection.start.line, character: 0 },
+        end,
+    }
+}
+
+#[allow(deprecated)]
+fn function_symbol(func: &FunctionDef, kind: SymbolKind) -> DocumentSymbol {
+    let selection = line_range(func.line, func.name_start_col, func.name_end_col);
+    DocumentSymbol {
+        name: func.name.clone(),
+        detail: func.return_type.as_ref().map(|r| format!("-> {r}")),
+        kind,
+        tags: None,
+        deprecated: None,
+        range: full_range(func.end_line, selection),
+        selection_range: selection,
+        children: None,
+    }
+}
+
+#[allow(deprecated)]
+fn class_symbol(class: &ClassDef) -> Option<DocumentSymbol> {
+    let methods: Vec<DocumentSymbol> = class
+        .methods
+        .iter()
+        .map(|m| function_symbol(m, SymbolKind::METHOD))
+        .collect();
+
+    // Si el analizador no informa la línea de la clase, usamos la de su primer método
+    let selection = match class.line {
+        Some(line) => line_range(line, class.name_start_col, class.name_end_col.max(class.name_start_col)),
+        None => {
+            let first = methods.iter().map(|m| m.range.start).min()?;
+            Range { start: first, end: first }
+        }
+    };
+    let mut range = full_range(class.end_line, selection);
+    if let Some(last) = methods.iter().map(|m| m.range.end).max()
+        && last > range.end
+    {
+        range.end = last;
+    }
+
+    Some(DocumentSymbol {
+        name: class.name.clone(),
+        detail: None,
+        kind: SymbolKind::CLASS,
+        tags: None,
+        deprecated: None,
+        range,
+        selection_range: selection,
+        children: Some(methods),
+    })
+}
+
+/// Arma el outline jerárquico de un archivo: clases (con sus métodos) y luego funciones top-level.
+pub fn document_symbols(analysis: &FileAnalysis) -> Vec<DocumentSymbol> {
+    let classes = analysis.classes.iter().filter_map(class_symbol);
+    let functions = analysis
+        .functions
+        .iter()
+        .map(|f| function_symbol(f, SymbolKind::FUNCTION));
+
+    classes.chain(functions).collect()
+}
+
+/// Puntúa `candidate` contra `query` como subsecuencia (sin distinguir mayúsculas).
+/// Retorna `None` si no matchea; a mayor puntaje, mejor match (consecutivos e inicios de palabra suman).
+pub fn fuzzy_score(query: &str, candidate: &str) -> Option<i64> {
+    if query.is_empty() {
+        return Some(0);
+    }
+    let candidate_chars: Vec<char> = candidate.chars().collect();
+    let mut score: i64 = 0;
+    let mut next = 0;
+    let mut previous_match: Option<usize> = None;
+
+    for q in query.chars().flat_map(|c| c.to_lowercase()) {
+        let idx = (next..candidate_chars.len())
+            .find(|&i| candidate_chars[i].to_lowercase().eq(std::iter::once(q)))?;
+
+        score += 1;
+        if previous_match.is_some_and(|p| p + 1 == idx) {
+            score += 5;
+        }
+        if idx == 0 || candidate_chars[idx - 1] == '_' || candidate_chars[idx].is_uppercase() {
+            score += 3;
+        }
+        previous_match = Some(idx);
+        next = idx + 1;
+    }
+
+    // Preferimos nombres cortos ante igual calidad de match
+    Some(score * 100 - candidate_chars.len() as i64)
+}
+
+/// Búsqueda fuzzy de funciones y métodos en todo el workspace sobre `functions_in_file`.
+#[allow(deprecated)]
+pub fn workspace_symbols(
+    functions_in_file: &[FunctionsInFiles],
+    query: &str,
+) -> Vec<SymbolInformation> {
+    let mut scored: Vec<(i64, &FunctionsInFiles)> = functions_in_file
+        .iter()
+        .filter_map(|f| Some((fuzzy_score(query, &f.symbol.name)?, f)))
+        .collect();
+    scored.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.symbol.name.cmp(&b.1.symbol.name)));
+
+    scored
+        .into_iter()
+        .take(WORKSPACE_SYMBOL_LIMIT)
+        .filter_map(|(_, f)| {
+            let uri = Url::from_file_path(&f.symbol.module).ok()?;
+            Some(SymbolInformation {
+                name: f.symbol.name.clone(),
+                kind: if f.symbol.class_name.is_some() {
+                    SymbolKind::METHOD
+                } else {
+                    SymbolKind::FUNCTION
+                },
+                tags: None,
+                deprecated: None,
+                location: Location {
+                    uri,
+                    range: line_range(f.line, f.name_start_col, f.name_end_col),
+                },
+                container_name: f.symbol.class_name.clone(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn document_symbols_nests_methods_under_classes() {
+        let analysis: FileAnalysis = serde_json::from_value(json!({
+            "imports": [],
+            "classes": [{
+                "name": "Order",
+                "line": 3, "name_start_col": 6, "name_end_col": 11,
+                "methods": [
+                    { "name": "total", "line": 4, "name_start_col": 8, "name_end_col": 13, "end_line": 6,
+                      "function_calls": [] }
+                ]
+            }],
+            "functions": [
+                { "name": "place_order", "line": 9, "name_start_col": 4, "name_end_col": 15, "function_calls": [] }
+            ]
+        }))
+        .unwrap();
+
+        let symbols = document_symbols(&analysis);
+        assert_eq!(symbols.len(), 2);
+        assert_eq!(symbols[0].kind, SymbolKind::CLASS);
+        let methods = symbols[0].children.as_ref().unwrap();
+        assert_eq!(methods[0].name, "total");
+        assert!(symbols[0].range.end >= methods[0].range.end);
+        assert_eq!(symbols[1].name, "place_order");
+        assert_eq!(symbols[1].selection_range.start, Position { line: 8, character: 4 });
+    }
+
+    #[test]
+    fn fuzzy_score_prefers_word_starts_and_rejects_non_matches() {
+        assert!(fuzzy_score("gdp", "get_discounted_price").is_some());
+        assert!(fuzzy_score("xyz", "get_discounted_price").is_none());
+        assert!(
+            fuzzy_score("place", "place_order").unwrap()
+                > fuzzy_score("place", "replace_all_entries").unwrap()
+        );
+    }
+}
@@ -1,6 +1,7 @@
+use crate::model::{FileAnalysis, FunctionDef};
+use crate::symbol::SymbolId;
 use crate::{Connections, FunctionsInFiles};
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
 use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
 
@@ -8,15 +9,15 @@ const RENAME_CONFIDENCE_THRESHOLD: f32 = 0.65;
 
 #[derive(Debug)]
 pub enum FunctionChange {
-    Added(String),
-    Removed(String),
+    Added(SymbolId),
+    Removed(SymbolId),
     Renamed {
-        old_name: String,
-        new_name: String,
+        old: SymbolId,
+        new: SymbolId,
         confidence: f32,
     },
     Modified {
-        name: String,
+        symbol: SymbolId,
         changes: Vec<SignatureChange>,
     },
 }
@@ -34,57 +35,25 @@ pub struct FileWarn {
   line: i64
 }
 
-/// Extrae el array de funciones del JSON analizado de un archivo.
-fn extract_functions(file_value: &Value) -> Vec<&Value> {
-    let mut result: Vec<&Value> = Vec::new();
-
-    // functions (nivel raíz)
-    if let Some(funcs) = file_value.get("functions").and_then(|f| f.as_array()) {
-        result.extend(funcs.iter());
-    }
-
-    // classes[].methods (puede no existir o estar vacío)
-    if let Some(classes) = file_value.get("classes").and_then(|c| c.as_array()) {
-        for class in classes {
-            if let Some(methods) = class.get("methods").and_then(|m| m.as_array()) {
-                result.extend(methods.iter());
-            }
-        }
-    }
-
-    result
-}
-/// Retorna el nombre de una función desde su `Value`.
-fn get_name(func: &Value) -> Option<&str> {
-    func.get("name")?.as_str()
-}
-
-/// Retorna la lista de nombres de parámetros de una función. Soporta tanto strings planos como objetos `{ "name": "..." }`.
-fn get_params(func: &Value) -> Vec<String> {
-    func.get("parameters")
-        .and_then(|p| p.as_array())
-        .map(|arr| {
-            arr.iter()
-                .filter_map(|p| {
-                    // Soporta tanto string plano como objeto { "name": "...", ... }
-                    p.as_str()
-                        .map(|s| s.to_string())
-                        .or_else(|| p.get("name")?.as_str().map(|s| s.to_string()))
-                })
-                .collect()
-        })
-        .unwrap_or_default()
+/// Extrae las funciones y métodos del análisis de un archivo, cada uno con su `SymbolId`.
+fn extract_functions<'a>(module: &str, analysis: &'a FileAnalysis) -> Vec<(SymbolId, &'a FunctionDef)> {
+    let functions = analysis
+        .functions
+        .iter()
+        .map(|func| (SymbolId::function(module, &func.name), func));
+    let methods = analysis.classes.iter().flat_map(|class| {
+        class
+            .methods
+            .iter()
+            .map(|method| (SymbolId::method(module, &class.name, &method.name), method))
+    });
+
+    functions.chain(methods).collect()
 }
 
-/// Retorna el tipo de retorno de una función como string, o `None` si es `null` o no existe.
-fn get_return_type(func: &Value) -> Option<String> {
-    func.get("return_type")
-        .filter(|v| !v.is_null())
-        .and_then(|v| {
-            v.as_str()
-                .map(|s| s.to_string())
-                .or_else(|| Some(v.to_string()))
-        })
+/// Retorna la lista de nombres de parámetros de una función.
+fn get_params(func: &FunctionDef) -> Vec<String> {
+    func.parameters.iter().map(|p| p.name.clone()).collect()
 }
 
 /// Calcula la similitud Jaccard entre dos listas de parámetros (0.0 = ninguno en común, 1.0 = idénticos).
@@ -122,12 +91,12 @@ fn name_similarity(a: &str, b: &str) -> f32 {
 
 /// Calcula el score de similitud ponderado entre dos funciones:
 /// 50% parámetros + 30% tipo de retorno + 20% nombre. Usado para detectar renombres.
-fn similarity_score(old_func: &Value, new_func: &Value) -> f32 {
+fn similarity_score(old_func: &FunctionDef, new_func: &FunctionDef) -> f32 {
     let old_params = get_params(old_func);
     let new_params = get_params(new_func);
     let param_sim = param_similarity(&old_params, &new_params);
 
-    let return_sim = match (get_return_type(old_func), get_return_type(new_func)) {
+    let return_sim = match (&old_func.return_type, &new_func.return_type) {
         (Some(a), Some(b)) => {
             if a == b {
                 1.0
@@ -139,20 +108,18 @@ fn similarity_score(old_func: &Value, new_func: &Value) -> f32 {
         _ => 0.3,
     };
 
-    let old_name = get_name(old_func).unwrap_or("");
-    let new_name = get_name(new_func).unwrap_or("");
-    let name_sim = name_similarity(old_name, new_name);
+    let name_sim = name_similarity(&old_func.name, &new_func.name);
 
     0.5 * param_sim + 0.3 * return_sim + 0.2 * name_sim
 }
 
 /// Compara dos definiciones de función y retorna la lista de cambios en su firma
 /// (tipo de retorno, parámetros agregados o eliminados).
-fn detect_signature_changes(old_func: &Value, new_func: &Value) -> Vec<SignatureChange> {
+fn detect_signature_changes(old_func: &FunctionDef, new_func: &FunctionDef) -> Vec<SignatureChange> {
     let mut changes = Vec::new();
 
-    let old_ret = get_return_type(old_func);
-    let new_ret = get_return_type(new_func);
+    let old_ret = old_func.return_type.clone();
+    let new_ret = new_func.return_type.clone();
     if old_ret != new_ret {
         changes.push(SignatureChange::ReturnTypeChanged {
             old: old_ret.unwrap_or_else(|| "None".to_string()),
@@ -179,102 +146,99 @@ fn detect_signature_changes(old_func: &Value, new_func: &Value) -> Vec<Signature
 
 /// Compara la versión actual de un archivo con la versión previa en el store y retorna
 /// los cambios detectados: funciones añadidas, eliminadas, renombradas o con firma modificada.
+/// Los símbolos se comparan por `SymbolId`, así métodos homónimos de distintas clases no se mezclan.
 pub fn detect_function_changes(
     file_path: &PathBuf,
-    current_value: &Value,
-    old_versions: &HashMap<PathBuf, Value>,
+    current: &FileAnalysis,
+    old_versions: &HashMap<PathBuf, FileAnalysis>,
 ) -> Vec<FunctionChange> {
     let mut changes = Vec::new();
+    let module = file_path.to_string_lossy();
 
     let old_file = match old_versions.get(file_path) {
         Some(v) => v,
         None => {
-            let added: Vec<FunctionChange> = extract_functions(current_value)
-                .iter()
-                .filter_map(|f| get_name(f).map(|n| FunctionChange::Added(n.to_string())))
+            let added: Vec<FunctionChange> = extract_functions(&module, current)
+                .into_iter()
+                .map(|(symbol, _)| FunctionChange::Added(symbol))
                 .collect();
             return added;
         }
     };
 
-    let old_funcs = extract_functions(old_file);
-    let new_funcs = extract_functions(current_value);
-
-    let old_by_name: HashMap<&str, &Value> = old_funcs
-        .iter()
-        .filter_map(|f| get_name(f).map(|n| (n, *f)))
-        .collect();
-    let new_by_name: HashMap<&str, &Value> = new_funcs
-        .iter()
-        .filter_map(|f| get_name(f).map(|n| (n, *f)))
-        .collect();
+    let old_by_symbol: HashMap<SymbolId, &FunctionDef> =
+        extract_functions(&module, old_file).into_iter().collect();
+    let new_by_symbol: HashMap<SymbolId, &FunctionDef> =
+        extract_functions(&module, current).into_iter().collect();
 
-    let mut matched_old: Vec<&str> = Vec::new();
-    let mut matched_new: Vec<&str> = Vec::new();
+    let mut matched: Vec<&SymbolId> = Vec::new();
 
-    for (name, old_func) in &old_by_name {
-        if let Some(new_func) = new_by_name.get(name) {
+    for (symbol, old_func) in &old_by_symbol {
+        if let Some(new_func) = new_by_symbol.get(symbol) {
             let sig_changes = detect_signature_changes(old_func, new_func);
             if !sig_changes.is_empty() {
                 changes.push(FunctionChange::Modified {
-                    name: name.to_string(),
+                    symbol: symbol.clone(),
                     changes: sig_changes,
                 });
             }
-            matched_old.push(name);
-            matched_new.push(name);
+            matched.push(symbol);
         }
     }
 
-    let orphan_old: Vec<(&str, &Value)> = old_by_name
+    let orphan_old: Vec<(&SymbolId, &FunctionDef)> = old_by_symbol
         .iter()
-        .filter(|(name, _)| !matched_old.contains(name))
-        .map(|(n, v)| (*n, *v))
+        .filter(|(symbol, _)| !matched.contains(symbol))
+        .map(|(s, v)| (s, *v))
         .collect();
 
-    let orphan_new: Vec<(&str, &Value)> = new_by_name
+    let orphan_new: Vec<(&SymbolId, &FunctionDef)> = new_by_symbol
         .iter()
-        .filter(|(name, _)| !matched_new.contains(name))
-        .map(|(n, v)| (*n, *v))
+        .filter(|(symbol, _)| !matched.contains(symbol))
+        .map(|(s, v)| (s, *v))
         .collect();
 
-    let mut scored_pairs: Vec<(f32, &str, &str)> = Vec::new();
+    let mut scored_pairs: Vec<(f32, &SymbolId, &SymbolId)> = Vec::new();
 
-    for (old_name, old_func) in &orphan_old {
-        for (new_name, new_func) in &orphan_new {
+    for (old_symbol, old_func) in &orphan_old {
+        // Un renombre no cambia la clase contenedora
+        for (new_symbol, new_func) in orphan_new
+            .iter()
+            .filter(|(new_symbol, _)| new_symbol.class_name == old_symbol.class_name)
+        {
             let score = similarity_score(old_func, new_func);
             if score >= RENAME_CONFIDENCE_THRESHOLD {
-                scored_pairs.push((score, old_name, new_name));
+                scored_pairs.push((score, old_symbol, new_symbol));
             }
         }
     }
 
-    scored_pairs.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+    scored_pairs.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap().then_with(|| a.1.cmp(b.1)));
 
-    let mut consumed_old: Vec<&str> = Vec::new();
-    let mut consumed_new: Vec<&str> = Vec::new();
+    let mut consumed_old: Vec<&SymbolId> = Vec::new();
+    let mut consumed_new: Vec<&SymbolId> = Vec::new();
 
-    for (score, old_name, new_name) in scored_pairs {
-        if consumed_old.contains(&old_name) || consumed_new.contains(&new_name) {
+    for (score, old_symbol, new_symbol) in scored_pairs {
+        if consumed_old.contains(&old_symbol) || consumed_new.contains(&new_symbol) {
             continue;
         }
         changes.push(FunctionChange::Renamed {
-            old_name: old_name.to_string(),
-            new_name: new_name.to_string(),
+            old: old_symbol.clone(),
+            new: new_symbol.clone(),
             confidence: score,
         });
-        consumed_old.push(old_name);
-        consumed_new.push(new_name);
+        consumed_old.push(old_symbol);
+        consumed_new.push(new_symbol);
     }
 
-    for (old_name, _) in &orphan_old {
-        if !consumed_old.contains(old_name) {
-            changes.push(FunctionChange::Removed(old_name.to_string()));
+    for (old_symbol, _) in &orphan_old {
+        if !consumed_old.contains(old_symbol) {
+            changes.push(FunctionChange::Removed((*old_symbol).clone()));
         }
     }
-    for (new_name, _) in &orphan_new {
-        if !consumed_new.contains(new_name) {
-            changes.push(FunctionChange::Added(new_name.to_string()));
+    for (new_symbol, _) in &orphan_new {
+        if !consumed_new.contains(new_symbol) {
+            changes.push(FunctionChange::Added((*new_symbol).clone()));
         }
     }
 
@@ -282,18 +246,17 @@ pub fn detect_function_changes(
 }
 
 /// Para cada función modificada/renombrada, devuelve los archivos que la usan
+/// (agrupados por el nombre para mostrar del símbolo).
 pub fn affected_files_by_change(
     changes: &[FunctionChange],
     connections: &[Connections],
-    file_src: &PathBuf,
 ) -> HashMap<String, Vec<FileWarn>> {
-    let path_str = file_src.to_str().unwrap().to_string();
-    let changed_functions: Vec<&str> = changes
+    let changed_symbols: Vec<&SymbolId> = changes
         .iter()
         .filter_map(|change| match change {
-            FunctionChange::Modified { name, .. } => Some(name.as_str()),
-            FunctionChange::Renamed { old_name, .. } => Some(old_name.as_str()),
-            FunctionChange::Removed(name) => Some(name.as_str()),
+            FunctionChange::Modified { symbol, .. } => Some(symbol),
+            FunctionChange::Renamed { old, .. } => Some(old),
+            FunctionChange::Removed(symbol) => Some(symbol),
             FunctionChange::Added(_) => None,
         })
         .collect();
@@ -302,10 +265,10 @@ pub fn affected_files_by_change(
 
     for conn in connections
         .iter()
-        .filter(|c| c.file_src == path_str && changed_functions.contains(&c.function.as_str()))
+        .filter(|c| changed_symbols.contains(&&c.target))
     {
         result
-            .entry(conn.function.clone())
+            .entry(conn.target.to_string())
             .or_default()
             .push(FileWarn { path: conn.file_use.clone(), line: conn.line.clone() } );
     }
@@ -319,15 +282,12 @@ pub fn find_unused_functions(
     functions_in_files: &[FunctionsInFiles],
     connections: &[Connections],
 ) -> Vec<FunctionsInFiles> {
-    let used: HashSet<(&str, &str)> = connections
-        .iter()
-        .map(|c| (c.file_src.as_str(), c.function.as_str()))
-        .collect();
+    let used: HashSet<&SymbolId> = connections.iter().map(|c| &c.target).collect();
 
     functions_in_files
         .iter()
-        .filter(|f| f.function != "main" && !f.function.starts_with('_'))
-        .filter(|f| !used.contains(&(f.file_src.as_str(), f.function.as_str())))
+        .filter(|f| f.symbol.name != "main" && !f.symbol.name.starts_with('_'))
+        .filter(|f| !used.contains(&f.symbol))
         .cloned()
         .collect()
 }
@@ -103,7 +103,7 @@ export class GraphCache {
     const grouped = new Map();
 
     for (const edge of this._edges) {
-      if (edge.type !== 'calls') continue;
+      if (edge.type !== 'calls' && edge.type !== 'instantiates') continue;
       if (!localFnIds.has(edge.source)) continue;
       if (seen.has(edge.target)) continue;
 
@@ -199,7 +199,7 @@ export class GraphCache {
     // Edges: fold endpoints inside collapsed folders to their visible ancestor
     const addedEdgeKeys = new Set();
     for (const edge of this._edges) {
-      if (edge.type !== 'calls' && edge.type !== 'imports') continue;
+      if (!['calls', 'imports', 'inherits', 'instantiates'].includes(edge.type)) continue;
 
       const src = getVisibleAncestor(edge.source);
       const tgt = getVisibleAncestor(edge.target);
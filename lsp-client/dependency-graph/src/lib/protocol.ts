@@ -16,6 +16,26 @@
 
 export interface TreeSitterData {
   files: FileData[];
+  /** Llamadas resueltas por el backend (las mismas que usan references y rename) */
+  edges?: GraphEdgeData[];
+}
+
+export interface GraphEdgeData {
+  /** Tipo de relación: llamada, herencia (subclase → base) o instanciación. Ausente en backends viejos = "calls" */
+  kind?: 'calls' | 'inherits' | 'instantiates';
+  /** ID del símbolo que llama (o de la subclase). Ej: "mth::main.py::Cart::checkout" */
+  source: string;
+  /** ID del símbolo llamado. Ej: "fn::projecto/product.py::make_product" */
+  target: string;
+  /** Archivo del call site, relativo al root del proyecto */
+  file: string;
+  /** Rango LSP del call site (0-based) */
+  range: {
+    start: { line: number, character: number };
+    end: { line: number, character: number };
+  };
+  /** true si el archivo destino fue borrado */
+  dangling: boolean;
 }
 
 export interface FileData {
@@ -69,7 +89,7 @@ export interface FunctionCallData {
 
 export type NodeType = 'folder' | 'file' | 'function' | 'method' | 'class';
 
-export type EdgeType = 'contains' | 'declares' | 'imports' | 'calls';
+export type EdgeType = 'contains' | 'declares' | 'imports' | 'calls' | 'inherits' | 'instantiates';
 
 /**
  * Formatos de ID del grafo interno:
@@ -116,6 +136,8 @@ export interface InternalGraph {
 
 export interface RenameRequest {
   filePath: string;
+  /** ID del nodo (fn::/mth::) — el backend lo mapea 1:1 a su SymbolId */
+  symbolId?: string;
   oldName: string;
   newName: string;
 }
@@ -147,6 +147,13 @@ export function buildGraphFromTreeSitter(data) {
     }
 
     // ── Pass 2: build all edges (all nodes exist now) ─────────────────────────
+    // Si el backend manda sus llamadas resueltas las usamos tal cual; la resolución
+    // local por import_name queda sólo como fallback para payloads sin `edges`.
+    const backendEdges = Array.isArray(data.edges);
+    if (backendEdges) {
+        buildBackendCallEdges(/** @type {import('./protocol').GraphEdgeData[]} */ (data.edges));
+    }
+
     for (const file of data.files) {
         if (!file.file_name) continue;
 
@@ -164,6 +171,8 @@ export function buildGraphFromTreeSitter(data) {
             link(imp.path, file.file_name, 'imports');
         }
 
+        if (backendEdges) continue;
+
         // Call edges from top-level functions
         for (const fn of file.functions ?? []) {
             const callerId = mkFnId(file.file_name, fn.name);
@@ -185,6 +194,28 @@ export function buildGraphFromTreeSitter(data) {
 
     // ── Call edge resolution ──────────────────────────────────────────────────
 
+    /**
+     * Un edge por par (origen, destino) y tipo, aunque haya varios call sites entre ellos.
+     * Las herencias (subclase → base) llegan con `kind: 'inherits'` y las instanciaciones
+     * (`Product(...)` → clase y `__init__`) con `kind: 'instantiates'`.
+     * @param {import('./protocol').GraphEdgeData[]} backendEdges
+     */
+    function buildBackendCallEdges(backendEdges) {
+        /** @type {Set<string>} */
+        const seen = new Set();
+        for (const edge of backendEdges) {
+            if (!nodes.has(edge.source) || !nodes.has(edge.target)) {
+                console.warn(`[treeSitterAdapter] edge con nodo inexistente: ${edge.source} -> ${edge.target}`);
+                continue;
+            }
+            const edgeType = edge.kind === 'inherits' || edge.kind === 'instantiates' ? edge.kind : 'calls';
+            const key = `${edgeType}|${edge.source}|${edge.target}`;
+            if (seen.has(key)) continue;
+            seen.add(key);
+            link(edge.source, edge.target, edgeType);
+        }
+    }
+
     /**
      * @param {import('./protocol').FunctionCallData[]} calls
      * @param {string} callerId
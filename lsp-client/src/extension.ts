@@ -11,8 +11,53 @@ import {
 let client: LanguageClient;
 
 let files: any;
+let edges: any;
 let activePanel: vscode.WebviewPanel | undefined;
 
+// Grafo recibido del backend: revisión, archivos por file_name y aristas por clave serializada
+type GraphSnapshot = { revision: number, files: any[], edges: any[] };
+type GraphDelta = {
+  revision: number,
+  base_revision: number,
+  added_files: any[],
+  updated_files: any[],
+  removed_files: string[],
+  added_edges: any[],
+  removed_edges: any[]
+};
+
+let graphRevision = 0;
+let graphFiles = new Map<string, any>();
+let graphEdges = new Map<string, any>();
+
+function loadSnapshot(snapshot: GraphSnapshot) {
+  graphRevision = snapshot.revision;
+  graphFiles = new Map(snapshot.files.map(file => [file.file_name, file]));
+  graphEdges = new Map(snapshot.edges.map(edge => [JSON.stringify(edge), edge]));
+}
+
+function applyDelta(delta: GraphDelta) {
+  for (const file of [...delta.added_files, ...delta.updated_files]) {
+    graphFiles.set(file.file_name, file);
+  }
+  delta.removed_files.forEach(name => graphFiles.delete(name));
+  delta.removed_edges.forEach(edge => graphEdges.delete(JSON.stringify(edge)));
+  delta.added_edges.forEach(edge => graphEdges.set(JSON.stringify(edge), edge));
+  graphRevision = delta.revision;
+}
+
+function postGraph() {
+  files = Array.from(graphFiles.values());
+  edges = Array.from(graphEdges.values());
+  if (activePanel) {
+    activePanel.webview.postMessage({
+      command: 'lsp-server/processedJson',
+      files: files,
+      edges: edges
+    });
+  }
+}
+
 export function activate(context: vscode.ExtensionContext) {
   const serverPath = context.asAbsolutePath(
     path.join("..", "lsp-backend", "target", "debug", "lsp-backend")
@@ -46,14 +91,23 @@ export function activate(context: vscode.ExtensionContext) {
   );
 
   client.start().then(() => {
-    client.onNotification("lsp-server/processedJson", (data: any) => {
-      files = data.files;
-      if (activePanel) {
-        activePanel.webview.postMessage({
-          command: 'lsp-server/processedJson',
-          files: files
-        });
+    client.onNotification("lsp-server/processedJson", (data: GraphSnapshot) => {
+      loadSnapshot(data);
+      postGraph();
+    });
+
+    client.onNotification("lsp-server/graphDelta", async (delta: GraphDelta) => {
+      // Deltas viejos (ya incluidos en un snapshot) se ignoran
+      if (delta.revision <= graphRevision) {
+        return;
+      }
+      if (delta.base_revision === graphRevision) {
+        applyDelta(delta);
+      } else {
+        // Nos perdimos una revisión: pedimos el grafo completo
+        loadSnapshot(await client.sendRequest<GraphSnapshot>("lsp-server/graphSnapshot"));
       }
+      postGraph();
     });
   });
 
@@ -85,6 +139,56 @@ export function activate(context: vscode.ExtensionContext) {
   });
 
 
+  // Los code lens del backend mandan argumentos LSP planos; los convertimos a tipos de VSCode
+  context.subscriptions.push(
+    vscode.commands.registerCommand(
+      "lsp-backend.showReferences",
+      (uri: string, position: any, locations: any[]) => {
+        const converter = client.protocol2CodeConverter;
+        return vscode.commands.executeCommand(
+          "editor.action.showReferences",
+          vscode.Uri.parse(uri),
+          converter.asPosition(position),
+          locations.map(location => converter.asLocation(location))
+        );
+      }
+    )
+  );
+
+  // Cancela el análisis en curso (escaneo inicial o re-análisis de un lote)
+  context.subscriptions.push(
+    vscode.commands.registerCommand("myLspServer.cancelAnalysis", () =>
+      client.sendRequest("lsp-server/cancelAnalysis")
+    )
+  );
+
+  // Vuelve a escanear el workspace (por ejemplo, después de cancelar el escaneo inicial)
+  context.subscriptions.push(
+    vscode.commands.registerCommand("myLspServer.rescanWorkspace", () =>
+      client.sendRequest("lsp-server/rescanWorkspace")
+    )
+  );
+
+  // Estado del backend: archivos analizados y los que quedaron en cuarentena por errores de análisis
+  context.subscriptions.push(
+    vscode.commands.registerCommand("myLspServer.showStatus", async () => {
+      const status = await client.sendRequest<{
+        analyzed_files: number,
+        connections: number,
+        quarantined: Array<{ path: string, reason: string, since: string }>
+      }>("lsp-server/status");
+      const summary = `${status.analyzed_files} files analyzed, ${status.connections} calls resolved`;
+      if (status.quarantined.length === 0) {
+        vscode.window.showInformationMessage(summary);
+        return;
+      }
+      status.quarantined.forEach(file => outputChannel.appendLine(`[quarantine] ${file.path}: ${file.reason}`));
+      vscode.window.showWarningMessage(
+        `${summary}. ${status.quarantined.length} file(s) quarantined (see "LSP Backend Logs")`
+      );
+    })
+  );
+
   vscode.window.showInformationMessage("LSP extension active!");
 
   const disposable = vscode.commands.registerCommand("myLspServer.showGraph", async () => {
@@ -129,7 +233,8 @@ export function activate(context: vscode.ExtensionContext) {
           if (files) {
             panel.webview.postMessage({
               command: 'lsp-server/processedJson',
-              files: files
+              files: files,
+              edges: edges
             });
           }
           // If files is null, the LSP notification will push data when it arrives
@@ -139,6 +244,7 @@ export function activate(context: vscode.ExtensionContext) {
           try {
             const result = await client.sendRequest('lsp-server/renameFunction', {
               file_path: message.filePath,
+              symbol_id: message.symbolId,
               old_name: message.oldName,
               new_name: message.newName
             });